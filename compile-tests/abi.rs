@@ -0,0 +1,23 @@
+pub type Printf = extern "C" fn(format: *const c_char, ...) -> c_int;
+
+pub type Callback = extern "stdcall" fn(data: *mut c_void);
+
+#[no_mangle]
+pub extern "C" fn set_handlers(log: Printf,
+                               callback: Callback,
+                               on_exit: Option<extern "stdcall" fn(code: i32)>) { }
+
+#[no_mangle]
+pub extern "system" fn window_proc(hwnd: *mut c_void, msg: u32) -> isize { 0 }
+
+#[no_mangle]
+pub extern "fastcall" fn fast_add(a: i32, b: i32) -> *mut i32 { }
+
+#[no_mangle]
+pub extern "C-unwind" fn may_unwind(x: u8) { }
+
+#[no_mangle]
+pub extern fn plain(x: u8) { }
+
+#[no_mangle]
+pub extern "Rust" fn not_exported(x: u8) { }
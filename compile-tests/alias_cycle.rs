@@ -0,0 +1,26 @@
+#[cfg(x)]
+pub type A = B;
+#[cfg(not(x))]
+pub type A = u8;
+#[cfg(x)]
+pub type B = u16;
+#[cfg(not(x))]
+pub type B = A;
+
+#[repr(C)]
+pub struct Pair<T> {
+    first: T,
+    second: T,
+}
+
+#[cfg(x)]
+pub type C<T> = D<T>;
+#[cfg(not(x))]
+pub type C<T> = Pair<T>;
+#[cfg(x)]
+pub type D<T> = Pair<T>;
+#[cfg(not(x))]
+pub type D<T> = C<T>;
+
+#[no_mangle]
+pub extern "C" fn root(a: A, b: B, c: C<i32>, d: D<i32>) { }
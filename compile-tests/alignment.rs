@@ -0,0 +1,28 @@
+#[repr(C, packed)]
+struct Packed {
+    tag: u8,
+    value: u32,
+}
+
+#[repr(C)]
+#[repr(packed(1))]
+struct PackedOne {
+    a: u8,
+    b: u64,
+}
+
+#[repr(C, align(16))]
+struct Aligned {
+    x: f32,
+    y: f32,
+}
+
+// Only packed(1) can be expressed, so this stays opaque
+#[repr(C, packed(4))]
+struct PackedFour {
+    a: u8,
+    b: u64,
+}
+
+#[no_mangle]
+pub extern "C" fn root(a: Packed, b: PackedOne, c: Aligned, d: *const PackedFour) { }
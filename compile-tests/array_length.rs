@@ -0,0 +1,14 @@
+const MAX_NAME_LEN: usize = 16;
+const GRID: usize = 4;
+pub const COLUMNS: usize = 8;
+
+#[repr(C)]
+struct Entry {
+    name: [u8; MAX_NAME_LEN],
+    matrix: [f32; GRID * GRID],
+    padding: [u8; (MAX_NAME_LEN + 1) / 2],
+    columns: [i32; COLUMNS],
+}
+
+#[no_mangle]
+pub extern "C" fn root(e: Entry) { }
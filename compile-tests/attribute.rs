@@ -0,0 +1,53 @@
+#[deprecated]
+#[repr(C)]
+struct OldPoint {
+    x: i32,
+    y: i32,
+}
+
+#[deprecated(since = "0.2.0", note = "use \"Mode\" instead")]
+#[repr(u8)]
+enum OldMode {
+    A,
+    B,
+}
+
+#[deprecated = "use Shape instead"]
+#[repr(C, u8)]
+enum OldShape {
+    Circle(f32),
+    Square(f32),
+}
+
+#[no_mangle]
+pub extern "C" fn abort_with(code: i32) -> ! {
+    loop {}
+}
+
+#[must_use]
+#[no_mangle]
+pub extern "C" fn try_open(path: *const u8) -> bool {
+    true
+}
+
+#[must_use = "closing can fail"]
+#[no_mangle]
+pub extern "C" fn close_all() {
+}
+
+#[cold]
+#[no_mangle]
+pub extern "C" fn report_error(code: i32) {
+}
+
+#[deprecated]
+#[no_mangle]
+pub extern "C" fn old_api(p: OldPoint, m: OldMode, s: OldShape) {
+}
+
+#[deprecated(note = "use try_open")]
+#[must_use]
+#[no_mangle]
+pub extern "C" fn open(path: *const u8) -> i32 {
+    0
+}
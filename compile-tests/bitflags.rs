@@ -0,0 +1,21 @@
+#[macro_use]
+extern crate bitflags;
+
+bitflags! {
+    /// Flags for opening a file
+    #[repr(C)]
+    pub struct OpenFlags: u32 {
+        const READ = 0b0001;
+        const WRITE = 0b0010;
+        const CREATE = 1 << 2;
+        const READ_WRITE = Self::READ.bits | Self::WRITE.bits;
+    }
+
+    pub struct Mode: u8 {
+        const EXECUTE = 0x1;
+        const ALL = !0;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn open(flags: OpenFlags, mode: Mode) -> OpenFlags { }
@@ -0,0 +1,43 @@
+#[cfg(windows)]
+#[repr(C)]
+struct Handle {
+    socket: u32,
+}
+
+#[cfg(not(windows))]
+#[repr(C)]
+struct Handle {
+    fd: i32,
+}
+
+#[repr(u8)]
+enum Backend {
+    Software,
+    #[cfg(feature = "gpu")]
+    Gpu,
+    #[cfg(all(unix, feature = "x11"))]
+    X11,
+    Headless,
+}
+
+#[repr(C)]
+struct Surface {
+    handle: Handle,
+    backend: Backend,
+    #[cfg(any(windows, feature = "x11"))]
+    scale: f32,
+}
+
+#[cfg(feature = "gpu")]
+mod gpu {
+    #[repr(C)]
+    pub struct Device {
+        id: u64,
+    }
+
+    #[no_mangle]
+    pub extern "C" fn gpu_device(surface: *const ::Surface) -> Device { }
+}
+
+#[no_mangle]
+pub extern "C" fn root(surface: Surface) { }
@@ -0,0 +1,39 @@
+const LEN: usize = 2;
+
+mod small {
+    const LEN: usize = 4;
+
+    #[repr(C)]
+    pub struct Small {
+        data: [u8; LEN],
+    }
+
+    #[repr(u8)]
+    pub enum Size {
+        Tiny = super::LEN as u8,
+        Medium = LEN as u8,
+        Big = ::large::LEN as u8,
+    }
+}
+
+mod large {
+    pub const LEN: usize = 16;
+
+    #[repr(C)]
+    pub struct Large {
+        data: [u8; LEN],
+        small: [u8; super::LEN * 2],
+    }
+}
+
+use small::{Small, Size};
+use large::Large;
+
+#[repr(C)]
+struct Root {
+    data: [u8; LEN],
+    large: [u8; large::LEN],
+}
+
+#[no_mangle]
+pub extern "C" fn root(a: Small, b: Large, c: Size, d: Root) { }
@@ -0,0 +1,21 @@
+pub const FOO: i32 = 10;
+pub const BAR: &'static str = "hello world";
+pub const NEG: i64 = -5;
+pub const BIG: u64 = 18446744073709551615;
+pub const MIN: i64 = -9223372036854775808;
+pub const PI: f64 = 3.141_592;
+pub const ENABLED: bool = true;
+pub const DELIMITER: char = ':';
+pub const QUOTED: &'static str = "say \"hi\"\n";
+pub const BYTE: u8 = b'x';
+
+const PRIVATE: i32 = 2;
+
+#[repr(C)]
+struct Foo {
+    x: [i32; 10],
+}
+
+#[no_mangle]
+extern "C" fn root(x: Foo)
+{ }
@@ -0,0 +1,76 @@
+/// The largest number of layers.
+pub const MAX_LAYERS: u32 = 8;
+
+/// A 2D point, in pixels.
+///
+/// cbindgen:derive-eq
+#[repr(C)]
+struct Point {
+    /// The distance from the left edge
+    x: f32,
+    /// The distance from the top edge
+    y: f32,
+}
+
+/** The ways a layer can be blended.
+ *
+ * See [`Layer`]. */
+#[repr(u32)]
+enum BlendMode {
+    /// Draw over what is below
+    Normal,
+    /// Multiply with what is below, see
+    /// [the spec](https://www.w3.org/TR/compositing-1/#blendingmultiply)
+    Multiply,
+}
+
+/// A shape in a layer.
+#[repr(C, u8)]
+enum Shape {
+    /// A circle, given by its radius
+    Circle(f32),
+    /// A rectangle
+    Rect { width: f32, height: f32 },
+}
+
+/// A layer of the scene.
+#[repr(C)]
+struct Layer {
+    origin: Point,
+    #[doc = "How the layer is blended, which is `Normal` by default"]
+    mode: BlendMode,
+    shape: Shape,
+}
+
+/// A path to save a layer to, which is a directory such as C:\
+#[repr(C)]
+struct LayerPath {
+    bytes: *const u8,
+    len: usize,
+}
+
+/// Saves a layer to a file in `path`.
+#[no_mangle]
+pub extern "C" fn save_layer(layer: *const Layer, path: LayerPath) {
+}
+
+/// Draws a layer in the range [0, 1] of the scene's opacity.
+///
+/// # Safety
+///
+/// `layer` must point to a valid `Layer`, which isn't freed until this
+/// returns.
+///
+/// # Examples
+///
+/// ```
+/// # let layer = Layer::default();
+/// draw_layer(&layer);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn draw_layer(layer: *const Layer) {
+}
+
+/// The number of layers drawn so far. Comments like `*/` are escaped.
+#[no_mangle]
+pub static mut LAYERS_DRAWN: u32 = 0;
@@ -27,9 +27,55 @@ enum C {
     c4 = 5,
 }
 
+#[repr(u64)]
+enum D {
+    d1 = 0,
+    d2 = 8589934592,
+    d3,
+}
+
+#[repr(i32)]
+enum E {
+    e1 = -2,
+    e2,
+    e3,
+    e4 = 10,
+}
+
+#[repr(i8)]
+enum F {
+    f1 = -128,
+    f2 = 127,
+}
+
+#[repr(isize)]
+enum G {
+    g1 = -1,
+    g2,
+}
+
+#[repr(usize)]
+enum H {
+    h1,
+    h2,
+}
+
+#[repr(C)]
+enum I {
+    i1,
+    i2 = 4,
+    i3,
+}
+
 #[no_mangle]
 extern "C" fn root(x: *mut Opaque,
                    y: A,
                    z: B,
-                   w: C)
+                   w: C,
+                   d: D,
+                   e: E,
+                   f: F,
+                   g: G,
+                   h: H,
+                   i: I)
 { }
@@ -0,0 +1,33 @@
+#[repr(C)]
+struct Point {
+    x: f32,
+    y: f32,
+}
+
+#[repr(u8)]
+enum Event {
+    Click(Point),
+    Scroll { dx: f32, dy: f32 },
+    Key(u32, bool),
+    Quit,
+}
+
+#[repr(C, u16)]
+enum Shape {
+    Circle { center: Point, radius: f32 },
+    Rect(Point, Point),
+    Empty,
+}
+
+#[repr(C)]
+enum Message {
+    Text(*const u8, u32),
+    Code(i32),
+    Nothing,
+}
+
+#[no_mangle]
+extern "C" fn root(e: Event,
+                   s: Shape,
+                   m: Message)
+{ }
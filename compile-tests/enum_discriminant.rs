@@ -0,0 +1,33 @@
+const BASE: i32 = 0x100;
+const MASK: u8 = !0 >> 4;
+pub const DERIVED: u32 = (BASE as u32 + 1) << 2;
+
+#[repr(i32)]
+enum Status {
+    Failed = -1,
+    Ok = 0,
+    Custom = BASE + 1,
+    Next,
+    Shifted = 1 << 4,
+    Combined = (1 << 1) | (1 << 2),
+}
+
+#[repr(u8)]
+enum Flags {
+    None = 0,
+    Low = MASK & 0x3,
+    High = MASK,
+    Letter = b'x',
+    All = !0,
+}
+
+#[repr(u32)]
+enum Unsupported {
+    A = foo(),
+}
+
+#[no_mangle]
+extern "C" fn root(x: Status,
+                   y: Flags,
+                   z: *mut Unsupported)
+{ }
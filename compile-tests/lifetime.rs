@@ -0,0 +1,34 @@
+#[repr(C)]
+struct Slice<'a, T> {
+    data: &'a T,
+    len: usize,
+    _marker: PhantomData<&'a [T]>,
+}
+
+#[repr(C)]
+struct Pair<A, B> {
+    first: A,
+    second: B,
+}
+
+#[repr(C)]
+struct Wrapper<T> {
+    inner: T,
+}
+
+#[repr(u8)]
+enum Borrowed<'a> {
+    Name(&'a u8),
+    Nothing,
+}
+
+type ByteSlice<'a> = Slice<'a, u8>;
+
+type NestedPair = Pair<Wrapper<i32>, Slice<'static, Pair<u8, u16>>>;
+
+type WrappedSlice<'a, T> = Wrapper<Slice<'a, T>>;
+
+type FloatSlice = WrappedSlice<'static, f32>;
+
+#[no_mangle]
+pub extern "C" fn root<'a>(a: ByteSlice<'a>, b: NestedPair, c: FloatSlice, d: Borrowed<'a>) { }
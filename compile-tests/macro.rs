@@ -0,0 +1,60 @@
+macro_rules! point {
+    ($name:ident, $t:ty) => {
+        #[repr(C)]
+        pub struct $name {
+            x: $t,
+            y: $t,
+        }
+    };
+}
+
+macro_rules! fields {
+    ($(#[$attr:meta])* $vis:vis struct $name:ident { $($field:ident: $t:ty),* $(,)? }) => {
+        $(#[$attr])*
+        $vis struct $name {
+            $($field: $t),*
+        }
+    };
+}
+
+macro_rules! getter {
+    ($name:ident -> $ret:ty = $value:expr) => {
+        #[no_mangle]
+        pub extern "C" fn $name() -> $ret {
+            $value
+        }
+    };
+}
+
+macro_rules! constants {
+    ($($name:ident = $value:expr;)+) => {
+        $(pub const $name: u32 = $value;)+
+    };
+}
+
+macro_rules! points {
+    ($($name:ident: $t:ty),*) => {
+        $(point!($name, $t);)*
+    };
+}
+
+point!(PointF, f32);
+points!(PointI: i32, PointU: Option<&'static u8>);
+
+fields! {
+    #[repr(C)]
+    pub struct Rect {
+        origin: PointF,
+        size: PointF,
+    }
+}
+
+constants! {
+    WIDTH = 1 + 1;
+    HEIGHT = WIDTH * 2;
+}
+
+getter!(origin -> PointF = PointF { x: 0.0, y: 0.0 });
+
+#[no_mangle]
+pub extern "C" fn root(a: Rect, b: PointI, c: PointU) { }
@@ -0,0 +1,30 @@
+mod types {
+    #[repr(C)]
+    pub struct Config {
+        verbose: bool,
+    }
+
+    pub mod nested {
+        #[repr(C)]
+        pub struct Handle {
+            id: u32,
+            config: super::Config,
+        }
+    }
+}
+
+mod other {
+    // Not used by the bindings, so it doesn't conflict with `types::Config`
+    #[repr(C)]
+    pub struct Config {
+        level: i32,
+    }
+}
+
+mod ffi {
+    use types::Config;
+    use types::nested::{self, Handle};
+
+    #[no_mangle]
+    pub extern "C" fn root(a: Config, b: *const Handle, c: nested::Handle, d: ::types::Config) { }
+}
@@ -0,0 +1,37 @@
+#[repr(C)]
+struct Foo<T> {
+    data: *const T,
+}
+
+#[repr(C)]
+struct Bar<T> {
+    foo: Foo<T>,
+    items: Foo<Foo<T>>,
+}
+
+#[repr(C)]
+union Either<A, B> {
+    a: A,
+    b: B,
+}
+
+#[repr(C)]
+struct Tuple<A, B> {
+    a: *const A,
+    b: *const B,
+}
+
+type Indirect<T> = Tuple<T, f32>;
+
+struct Opaque<T> {
+    inner: T,
+}
+
+#[no_mangle]
+pub extern "C" fn root(a: Foo<i32>,
+                       b: Foo<f32>,
+                       c: Bar<u8>,
+                       d: Either<i32, f64>,
+                       e: Indirect<u16>,
+                       f: *const Opaque<i64>,
+                       g: Tuple<Foo<u8>, *mut i32>) { }
@@ -0,0 +1,8 @@
+#[repr(C)]
+struct List<T> {
+    value: T,
+    next: *const List<*const T>,
+}
+
+#[no_mangle]
+pub extern "C" fn root(a: List<i32>) { }
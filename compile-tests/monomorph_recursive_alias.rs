@@ -0,0 +1,12 @@
+#[repr(C)]
+struct Tree<T> {
+    value: T,
+    children: *const Forest<*const T>,
+}
+
+type Forest<T> = Tree<T>;
+
+type IntTree = Forest<i32>;
+
+#[no_mangle]
+pub extern "C" fn root(a: IntTree) { }
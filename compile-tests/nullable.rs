@@ -0,0 +1,23 @@
+use std::ptr::NonNull;
+
+#[repr(C)]
+struct Foo {
+    a: i32,
+}
+
+#[repr(C)]
+struct Callbacks {
+    on_event: Option<extern "C" fn(i32) -> bool>,
+    on_close: extern "C" fn(),
+}
+
+#[no_mangle]
+pub extern "C" fn root(a: Option<&Foo>,
+                       b: Option<&mut Foo>,
+                       c: NonNull<Foo>,
+                       d: Option<NonNull<Foo>>,
+                       e: Box<Foo>,
+                       f: Option<Box<Foo>>,
+                       g: &Foo,
+                       h: *const Foo,
+                       i: Callbacks) { }
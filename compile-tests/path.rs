@@ -0,0 +1,16 @@
+#[repr(C)]
+struct Handle {
+    id: u32,
+}
+
+#[repr(C)]
+struct Buffer {
+    data: *mut ::std::os::raw::c_char,
+    flags: core::ffi::c_int,
+    len: libc::c_ulong,
+    handle: self::Handle,
+    other: ::Handle,
+}
+
+#[no_mangle]
+pub extern "C" fn root(a: Buffer, b: *const std::os::raw::c_void, c: std::primitive::u8) { }
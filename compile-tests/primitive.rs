@@ -0,0 +1,16 @@
+use std::num::NonZeroU32;
+
+#[repr(C)]
+struct Sizes {
+    a: usize,
+    b: isize,
+    c: char,
+    d: NonZeroU32,
+    e: std::os::raw::c_long,
+    f: *mut std::ffi::c_void,
+    g: u128,
+    h: i128,
+}
+
+#[no_mangle]
+pub extern "C" fn root(s: *const Sizes, len: usize, offset: isize) -> char { }
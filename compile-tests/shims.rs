@@ -0,0 +1,54 @@
+/// A builder of display lists.
+///
+/// cbindgen:ffi-prefix=wr_builder
+impl Builder {
+    /// Makes an empty builder for a pipeline.
+    pub fn new(pipeline_id: u32) -> Builder {
+        Builder { items: Vec::new(), pipeline_id: pipeline_id }
+    }
+
+    /// Makes a builder with room for `capacity` items.
+    pub fn with_capacity(pipeline_id: u32, capacity: usize) -> Self {
+        Builder { items: Vec::with_capacity(capacity), pipeline_id: pipeline_id }
+    }
+
+    /// The number of items built so far.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn push_rect(&mut self, rect: Rect) {
+        self.items.push(rect);
+    }
+
+    /// The bounds of every item.
+    pub fn bounds(&self) -> Rect {
+        Rect { x: 0.0, y: 0.0, width: 0.0, height: 0.0 }
+    }
+
+    pub fn finish(self) -> u32 {
+        self.pipeline_id
+    }
+
+    pub fn default_pipeline() -> u32 {
+        0
+    }
+
+    fn reserve(&mut self, n: usize) {
+        self.items.reserve(n);
+    }
+}
+
+pub struct Builder {
+    items: Vec<Rect>,
+    pipeline_id: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Rect {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
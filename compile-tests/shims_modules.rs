@@ -0,0 +1,73 @@
+mod geom {
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct Rect {
+        pub x: f32,
+        pub y: f32,
+        pub width: f32,
+        pub height: f32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct Point {
+        pub x: f32,
+        pub y: f32,
+    }
+}
+
+mod display {
+    pub mod builder {
+        use geom::Rect;
+        use geom::Point as Position;
+
+        /// A builder of rects.
+        ///
+        /// cbindgen:ffi-prefix=rect_builder
+        impl Builder {
+            pub fn new() -> Builder {
+                Builder { rects: Vec::new() }
+            }
+
+            pub fn push(&mut self, rect: Rect) {
+                self.rects.push(rect);
+            }
+
+            pub fn move_to(&mut self, position: Position) {
+                for rect in &mut self.rects {
+                    rect.x = position.x;
+                    rect.y = position.y;
+                }
+            }
+
+            pub fn first(&self) -> &Rect {
+                &self.rects[0]
+            }
+
+            pub fn last_mut(&mut self) -> &mut Rect {
+                self.rects.last_mut().unwrap()
+            }
+
+            pub fn describe(&self) -> String {
+                format!("{} rects", self.rects.len())
+            }
+        }
+
+        pub struct Builder {
+            rects: Vec<Rect>,
+        }
+    }
+}
+
+mod inner {
+    /// cbindgen:ffi-prefix=counter
+    impl Counter {
+        pub fn new() -> Counter {
+            Counter { count: 0 }
+        }
+    }
+
+    struct Counter {
+        count: u32,
+    }
+}
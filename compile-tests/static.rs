@@ -0,0 +1,28 @@
+#[repr(C)]
+struct Bar {
+    x: i32,
+    y: f32,
+}
+
+struct Opaque;
+
+#[no_mangle]
+pub static NUMBER: i32 = 10;
+
+#[no_mangle]
+pub static mut COUNTER: u64 = 0;
+
+#[no_mangle]
+pub static BAR: Bar = Bar { x: 1, y: 2.0 };
+
+#[no_mangle]
+pub static mut OPAQUE: *mut Opaque = 0 as *mut Opaque;
+
+#[no_mangle]
+pub static TABLE: [u8; 4] = [1, 2, 3, 4];
+
+static PRIVATE: i32 = 2;
+
+#[no_mangle]
+extern "C" fn root()
+{ }
@@ -0,0 +1,11 @@
+/// A box to draw, which isn't the std `Box`.
+#[repr(C)]
+pub struct Box<T> {
+    width: T,
+    height: T,
+}
+
+#[no_mangle]
+pub extern "C" fn area(shape: Box<f32>, border: std::boxed::Box<f32>) -> f32 {
+    shape.width * shape.height + *border
+}
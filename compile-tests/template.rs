@@ -0,0 +1,32 @@
+#[repr(C)]
+struct Foo<T> {
+    data: *const T,
+    len: usize,
+}
+
+#[repr(C)]
+struct Pair<A, B> {
+    first: A,
+    second: Foo<B>,
+}
+
+#[repr(C)]
+union Either<A, B> {
+    a: A,
+    b: B,
+}
+
+struct Opaque<T> {
+    value: T,
+}
+
+type IntFoo = Foo<i32>;
+
+type FloatPair<T> = Pair<f32, T>;
+
+#[no_mangle]
+pub extern "C" fn root(a: IntFoo,
+                       b: Pair<u8, *const u16>,
+                       c: FloatPair<i64>,
+                       d: Either<i32, f64>,
+                       e: *const Opaque<i64>) { }
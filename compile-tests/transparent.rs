@@ -0,0 +1,20 @@
+#[repr(transparent)]
+struct Handle(u64);
+
+#[repr(transparent)]
+struct Meters {
+    value: f32,
+    _unit: PhantomData<Unit>,
+}
+
+#[repr(transparent)]
+struct Wrapper<T>(T);
+
+type IntWrapper = Wrapper<i32>;
+
+// Not one field, so this stays opaque
+#[repr(transparent)]
+struct Empty;
+
+#[no_mangle]
+pub extern "C" fn root(a: Handle, b: Meters, c: IntWrapper, d: *const Empty) -> Handle { }
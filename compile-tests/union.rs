@@ -0,0 +1,39 @@
+#[repr(C)]
+struct Point {
+    x: f32,
+    y: f32,
+}
+
+#[repr(C)]
+union Value {
+    integer: i32,
+    real: f32,
+    point: Point,
+}
+
+#[repr(C)]
+union Either<T> {
+    left: T,
+    right: u64,
+}
+
+type IntEither = Either<i32>;
+
+/// cbindgen:rename-all=GeckoCase
+#[repr(C)]
+union Renamed {
+    first_value: u8,
+    second_value: u16,
+}
+
+union Opaque {
+    a: i32,
+    b: f32,
+}
+
+#[no_mangle]
+extern "C" fn root(a: Value,
+                   b: IntEither,
+                   c: Renamed,
+                   d: *mut Opaque)
+{ }
@@ -0,0 +1,33 @@
+mod inner {
+    #[repr(C)]
+    pub struct Handle {
+        id: u32,
+    }
+
+    pub mod shapes {
+        #[repr(C)]
+        pub struct Rect {
+            width: f32,
+            height: f32,
+        }
+
+        #[repr(C)]
+        pub struct Circle {
+            radius: f32,
+        }
+    }
+}
+
+mod api {
+    pub use inner::shapes::*;
+    pub use inner::Handle;
+}
+
+mod ffi {
+    use inner::Handle as RawHandle;
+    use inner::shapes::{Rect as Area, Circle};
+    use api;
+
+    #[no_mangle]
+    pub extern "C" fn root(a: RawHandle, b: Area, c: Circle, d: api::Handle, e: api::Rect) { }
+}
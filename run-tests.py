@@ -5,11 +5,28 @@ import glob
 import subprocess
 import sys
 
-def cbindgen(rust_src, out, c):
+def cbindgen(rust_src, out, c, config, shims):
+    args = ["cargo", "run", "--"]
+    if config:
+        args += ["--config", config]
     if c:
-        subprocess.check_output(["cargo", "run", "--", "--lang", "c", rust_src, "-o", out])
-    else:
-        subprocess.check_output(["cargo", "run", "--", rust_src, "-o", out])
+        args += ["--lang", "c"]
+    if shims:
+        args += ["--shims", shims]
+    subprocess.check_output(args + [rust_src, "-o", out])
+
+def rustc(rust_src, shims):
+    # The shims are included in the crate that they were generated from
+    crate = "compile-tests/tmp.rs"
+    with open(crate, "w") as f:
+        f.write("include!(\"%s\");\ninclude!(\"%s\");\n" % (os.path.basename(rust_src),
+                                                          os.path.basename(shims)))
+    try:
+        subprocess.check_output(["rustc", "--crate-type", "lib", "--emit", "metadata",
+                                 "-A", "warnings", "-o", "compile-tests/tmp.rmeta", crate])
+        os.remove("compile-tests/tmp.rmeta")
+    finally:
+        os.remove(crate)
 
 def gcc(src):
     subprocess.check_output(["gcc", "-c", src, "-o", "compile-tests/tmp.o"])
@@ -19,25 +36,38 @@ def gxx(src):
     subprocess.check_output(["g++", "-c", src, "-o", "compile-tests/tmp.o"])
     os.remove("compile-tests/tmp.o")
 
-def run_compile_test(rust_src, leave_output, c):
+def run_compile_test(rust_src, leave_output, c, config):
     if c:
         out = rust_src.replace(".rs", ".c")
     else:
         out = rust_src.replace(".rs", ".cpp")
 
+    # Tests with `ffi-prefix` impls also check that their shims compile
+    shims = None
+    with open(rust_src) as f:
+        if "cbindgen:ffi-prefix" in f.read():
+            shims = rust_src.replace(".rs", "_shims.rs.out")
+
     try:
-        cbindgen(rust_src, out, c)
+        cbindgen(rust_src, out, c, config, shims)
 
         if c:
             gcc(out)
         else:
             gxx(out)
 
+        if shims:
+            rustc(rust_src, shims)
+
         if not leave_output:
             os.remove(out)
+            if shims:
+                os.remove(shims)
     except subprocess.CalledProcessError:
-        if not leave_output and os.path.exists(out):
-            os.remove(out)
+        if not leave_output:
+            for path in [out, shims]:
+                if path and os.path.exists(path):
+                    os.remove(path)
         return False
 
     return True
@@ -56,11 +86,20 @@ for flag in flags:
         c = True
 
 for test in tests:
-    if run_compile_test(test, leave_output, c):
-        num_pass += 1
-        print("Pass - %s" % test)
-    else:
-        num_fail += 1
-        print("Fail - %s" % test)
+    # A test can have its own config next to it, such as `cfg.toml` for
+    # `cfg.rs`, and is then run both with it and with the default config
+    configs = [None]
+    config = test.replace(".rs", ".toml")
+    if os.path.exists(config):
+        configs.append(config)
+
+    for config in configs:
+        name = test if config is None else "%s (%s)" % (test, config)
+        if run_compile_test(test, leave_output, c, config):
+            num_pass += 1
+            print("Pass - %s" % name)
+        else:
+            num_fail += 1
+            print("Fail - %s" % name)
 
 print("Tests complete. %i passed, %i failed." % (num_pass, num_fail))
@@ -0,0 +1,223 @@
+use syn;
+use syn::{DelimToken, Token, TokenTree};
+
+use bindgen::library::ConvertResult;
+use bindgen::utilities::tokens_to_string;
+
+// `bitflags!` structs only become real items once the macro is expanded,
+// so the invocation is parsed directly. Each struct in it is rewritten
+// into plain items that the rest of cbindgen understands:
+//
+//   bitflags! {                               struct Flags { bits: u32 }
+//       struct Flags: u32 {             =>    const A: u32 = 0b01;
+//           const A = 0b01;                   const B: u32 = A | 0b10;
+//           const B = Self::A.bits | 0b10;
+//       }
+//   }
+
+/// A struct declared by a `bitflags!` invocation.
+pub struct BitflagsDecl {
+    /// `struct Name { bits: Ty }`, with the attributes and visibility of
+    /// the declaration
+    pub item: syn::Item,
+    /// The integer type of the flags
+    pub ty: syn::Ty,
+    /// A `const` item for each flag, where references to other flags as
+    /// `Self::FLAG.bits` or `Name::FLAG.bits()` are replaced by `FLAG`
+    pub flags: Vec<syn::Item>,
+}
+
+/// Whether a macro invocation is a `bitflags!` invocation.
+pub fn is_bitflags(mac: &syn::Mac) -> bool {
+    match mac.path.segments.last() {
+        Some(segment) => segment.ident == "bitflags",
+        None => false,
+    }
+}
+
+/// Parses the structs declared by a `bitflags!` invocation.
+pub fn parse(mac: &syn::Mac) -> ConvertResult<Vec<BitflagsDecl>> {
+    let body = match mac.tts.first() {
+        Some(&TokenTree::Delimited(ref body)) if mac.tts.len() == 1 => &body.tts,
+        _ => return Err(format!("malformed bitflags! invocation")),
+    };
+
+    let mut tts = &body[..];
+    let mut result = Vec::new();
+
+    while !tts.is_empty() {
+        let attrs = take_attrs(&mut tts);
+        let vis = take_vis(&mut tts);
+
+        expect_keyword(&mut tts, "struct")?;
+        let name = take_ident(&mut tts)?;
+        expect_token(&mut tts, Token::Colon)?;
+
+        let ty_end = tts.iter()
+                        .position(|x| match x {
+                            &TokenTree::Delimited(ref x) => x.delim == DelimToken::Brace,
+                            _ => false,
+                        })
+                        .ok_or_else(|| format!("expected the flags of {}", name))?;
+        let ty = tokens_to_string(&tts[..ty_end]);
+        let flags = match &tts[ty_end] {
+            &TokenTree::Delimited(ref x) => &x.tts,
+            _ => unreachable!(),
+        };
+        tts = &tts[ty_end + 1..];
+
+        result.push(BitflagsDecl {
+            item: syn::parse_item(&format!("{} {} struct {} {{ bits: {} }}", attrs, vis, name, ty))?,
+            ty: syn::parse_type(&ty)?,
+            flags: parse_flags(&name, &ty, flags)?,
+        });
+    }
+
+    Ok(result)
+}
+
+fn parse_flags(name: &str, ty: &str, tts: &[TokenTree]) -> ConvertResult<Vec<syn::Item>> {
+    let mut tts = tts;
+    let mut result = Vec::new();
+
+    while !tts.is_empty() {
+        let attrs = take_attrs(&mut tts);
+
+        expect_keyword(&mut tts, "const")?;
+        // bitflags 2 allows `const _ = !0;` to mark every bit as known
+        let flag = match tts.first() {
+            Some(&TokenTree::Token(Token::Underscore)) => {
+                tts = &tts[1..];
+                None
+            }
+            _ => Some(take_ident(&mut tts)?),
+        };
+        expect_token(&mut tts, Token::Eq)?;
+
+        let expr_end = tts.iter()
+                          .position(|x| *x == TokenTree::Token(Token::Semi))
+                          .unwrap_or(tts.len());
+        let expr = tokens_to_string(&replace_flag_refs(name, &tts[..expr_end]));
+        tts = &tts[expr_end..];
+        if !tts.is_empty() {
+            tts = &tts[1..];
+        }
+
+        if let Some(flag) = flag {
+            result.push(syn::parse_item(&format!("{} const {}: {} = {};", attrs, flag, ty, expr))?);
+        }
+    }
+
+    Ok(result)
+}
+
+/// Replaces `Self::FLAG.bits`, `Name::FLAG.bits` and the `.bits()` forms
+/// of them with `FLAG`.
+fn replace_flag_refs(name: &str, tts: &[TokenTree]) -> Vec<TokenTree> {
+    let mut result = Vec::new();
+    let mut i = 0;
+
+    while i < tts.len() {
+        if let (Some(&TokenTree::Token(Token::Ident(ref owner))),
+                Some(&TokenTree::Token(Token::ModSep)),
+                Some(&TokenTree::Token(Token::Ident(ref flag))),
+                Some(&TokenTree::Token(Token::Dot)),
+                Some(&TokenTree::Token(Token::Ident(ref bits)))) = (tts.get(i),
+                                                                  tts.get(i + 1),
+                                                                  tts.get(i + 2),
+                                                                  tts.get(i + 3),
+                                                                  tts.get(i + 4)) {
+            if (owner == "Self" || owner == name) && bits == "bits" {
+                result.push(TokenTree::Token(Token::Ident(flag.clone())));
+                i += 5;
+
+                if let Some(&TokenTree::Delimited(ref call)) = tts.get(i) {
+                    if call.delim == DelimToken::Paren && call.tts.is_empty() {
+                        i += 1;
+                    }
+                }
+                continue;
+            }
+        }
+
+        match &tts[i] {
+            &TokenTree::Delimited(ref x) => {
+                result.push(TokenTree::Delimited(syn::Delimited {
+                    delim: x.delim,
+                    tts: replace_flag_refs(name, &x.tts),
+                }));
+            }
+            x => result.push(x.clone()),
+        }
+        i += 1;
+    }
+
+    result
+}
+
+/// Takes the outer attributes and doc comments at the start of `tts`.
+fn take_attrs(tts: &mut &[TokenTree]) -> String {
+    let mut attrs = Vec::new();
+
+    loop {
+        match (tts.get(0), tts.get(1)) {
+            (Some(&TokenTree::Token(Token::Pound)), Some(&TokenTree::Delimited(ref x)))
+                if x.delim == DelimToken::Bracket => {
+                attrs.extend_from_slice(&tts[..2]);
+                *tts = &tts[2..];
+            }
+            (Some(&TokenTree::Token(Token::DocComment(_))), _) => {
+                attrs.push(tts[0].clone());
+                *tts = &tts[1..];
+            }
+            _ => break,
+        }
+    }
+
+    tokens_to_string(&attrs)
+}
+
+/// Takes a `pub` or `pub(..)` at the start of `tts`.
+fn take_vis(tts: &mut &[TokenTree]) -> String {
+    match tts.first() {
+        Some(&TokenTree::Token(Token::Ident(ref x))) if x == "pub" => { }
+        _ => return String::new(),
+    }
+
+    let end = match tts.get(1) {
+        Some(&TokenTree::Delimited(ref x)) if x.delim == DelimToken::Paren => 2,
+        _ => 1,
+    };
+    let vis = tokens_to_string(&tts[..end]);
+    *tts = &tts[end..];
+    vis
+}
+
+fn take_ident(tts: &mut &[TokenTree]) -> ConvertResult<String> {
+    match tts.first() {
+        Some(&TokenTree::Token(Token::Ident(ref x))) => {
+            *tts = &tts[1..];
+            Ok(x.to_string())
+        }
+        _ => Err(format!("expected an identifier in bitflags! invocation")),
+    }
+}
+
+fn expect_keyword(tts: &mut &[TokenTree], keyword: &str) -> ConvertResult<()> {
+    match tts.first() {
+        Some(&TokenTree::Token(Token::Ident(ref x))) if x == keyword => {
+            *tts = &tts[1..];
+            Ok(())
+        }
+        _ => Err(format!("expected `{}` in bitflags! invocation", keyword)),
+    }
+}
+
+fn expect_token(tts: &mut &[TokenTree], token: Token) -> ConvertResult<()> {
+    if tts.first() == Some(&TokenTree::Token(token)) {
+        *tts = &tts[1..];
+        Ok(())
+    } else {
+        Err(format!("unexpected token in bitflags! invocation"))
+    }
+}
@@ -3,11 +3,13 @@ use std::path::Path;
 use std::process::Command;
 use std::str::from_utf8;
 
+use bindgen::config::ExpandStrategy;
+
 type ExpandResult = Result<String, String>;
 
-pub fn expand(manifest_path: &Path, crate_name: &str) -> ExpandResult {
+pub fn expand(manifest_path: &Path, crate_name: &str, strategy: ExpandStrategy) -> ExpandResult {
     let cargo = env::var("CARGO").unwrap_or_else(|_| String::from("cargo"));
-    let mut cmd = Command::new(cargo);
+    let mut cmd = Command::new(&cargo);
     cmd.arg("rustc");
     cmd.arg("--manifest-path");
     cmd.arg(manifest_path);
@@ -16,16 +18,29 @@ pub fn expand(manifest_path: &Path, crate_name: &str) -> ExpandResult {
     cmd.arg(crate_name);
     cmd.arg("--");
     cmd.arg("-Z");
-    cmd.arg("unstable-options");
-    cmd.arg("--pretty=expanded");
-    let output = cmd.output().unwrap();
+    cmd.arg("unpretty=expanded");
+
+    if strategy == ExpandStrategy::Bootstrap {
+        // Lets a stable rustc accept `-Z` options
+        cmd.env("RUSTC_BOOTSTRAP", "1");
+    }
+
+    let output = cmd.output().map_err(|e| {
+        format!("expanding crate `{}`: couldn't run `{}`: {}", crate_name, cargo, e)
+    })?;
 
-    let src = from_utf8(&output.stdout).unwrap().to_owned();
-    let error = from_utf8(&output.stderr).unwrap().to_owned();
+    let src = from_utf8(&output.stdout).map_err(|_| {
+        format!("expanding crate `{}`: rustc output isn't valid UTF-8", crate_name)
+    })?;
+    let error = String::from_utf8_lossy(&output.stderr);
 
-    if src.len() == 0 {
-        Err(error)
+    if !output.status.success() || src.is_empty() {
+        let hint = match strategy {
+            ExpandStrategy::Nightly => " (expanding needs a nightly toolchain, or the `bootstrap` strategy)",
+            ExpandStrategy::Bootstrap => "",
+        };
+        Err(format!("expanding crate `{}` failed{}:\n{}", crate_name, hint, error))
     } else {
-        Ok(src)
+        Ok(src.to_owned())
     }
 }
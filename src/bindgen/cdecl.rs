@@ -1,5 +1,6 @@
 use std::io::Write;
 
+use bindgen::config::Config;
 use bindgen::items::*;
 use bindgen::writer::*;
 
@@ -16,9 +17,12 @@ struct CDecl {
 }
 
 enum CDeclarator {
-    Ptr(bool),
-    Array(u64),
-    Func(Vec<(Option<String>, CDecl)>),
+    /// A pointer, whether it is const, and whether it can be null
+    Ptr(bool, bool),
+    Array(String),
+    /// A function's args, whether it is variadic, and the macro of its
+    /// calling convention
+    Func(Vec<(Option<String>, CDecl)>, bool, Option<String>),
 }
 
 impl CDecl {
@@ -30,33 +34,46 @@ impl CDecl {
         }
     }
 
-    fn from_type(t: &Type) -> CDecl {
+    fn from_type(t: &Type, config: &Config) -> CDecl {
         let mut cdecl = CDecl::new();
-        cdecl.build_type(t, false);
+        cdecl.build_type(t, false, config);
         cdecl
     }
-    fn from_func(f: &Function) -> CDecl {
+    fn from_const_type(t: &Type, config: &Config) -> CDecl {
         let mut cdecl = CDecl::new();
-        cdecl.build_func(f);
+        cdecl.build_type(t, true, config);
+        cdecl
+    }
+    fn from_func(f: &Function, config: &Config) -> CDecl {
+        let mut cdecl = CDecl::new();
+        cdecl.build_func(f, config);
         cdecl
     }
 
-    fn build_func(&mut self, f: &Function) {
-        let args = f.args.iter().map(|&(ref arg_name, ref arg_ty)| (Some(arg_name.clone()), CDecl::from_type(arg_ty))).collect();
-        self.declarators.push(CDeclarator::Func(args));
-        self.build_type(&f.ret, false);
+    fn build_func(&mut self, f: &Function, config: &Config) {
+        let args = f.args.iter().map(|&(ref arg_name, ref arg_ty)| (Some(arg_name.clone()), CDecl::from_type(arg_ty, config))).collect();
+        let convention = config.function.calling_convention(f.abi).cloned();
+        self.declarators.push(CDeclarator::Func(args, f.variadic, convention));
+        self.build_type(&f.ret, false, config);
     }
 
-    fn build_type(&mut self, t: &Type, is_const: bool) {
+    fn build_type(&mut self, t: &Type, is_const: bool, config: &Config) {
         match t {
-            &Type::Path(ref p) => {
+            &Type::Path(ref p, ref generics) => {
                 if is_const {
                     assert!(self.type_qualifers.len() == 0);
                     self.type_qualifers = "const".to_owned();
                 }
 
                 assert!(self.type_name.len() == 0);
-                self.type_name = p.clone();
+                self.type_name = if config.generics_as_templates() && !generics.is_empty() {
+                    let args = generics.iter()
+                                       .map(|x| CDecl::from_type(x, config).to_string(None, config))
+                                       .collect::<Vec<_>>();
+                    format!("{}<{}>", p, args.join(", "))
+                } else {
+                    mangle_path(p, generics, config)
+                };
             }
             &Type::Primitive(ref p) => {
                 if is_const {
@@ -65,31 +82,32 @@ impl CDecl {
                 }
 
                 assert!(self.type_name.len() == 0);
-                self.type_name = p.to_string();
+                self.type_name = p.c_name(config);
             }
 
-            &Type::ConstPtr(ref t)  => {
-                self.declarators.push(CDeclarator::Ptr(is_const));
-                self.build_type(t, true);
+            &Type::ConstPtr(ref t, nullable)  => {
+                self.declarators.push(CDeclarator::Ptr(is_const, nullable));
+                self.build_type(t, true, config);
             }
-            &Type::Ptr(ref t) => {
-                self.declarators.push(CDeclarator::Ptr(is_const));
-                self.build_type(t, false);
+            &Type::Ptr(ref t, nullable) => {
+                self.declarators.push(CDeclarator::Ptr(is_const, nullable));
+                self.build_type(t, false, config);
             }
-            &Type::Array(ref t, sz) => {
-                self.declarators.push(CDeclarator::Array(sz));
-                self.build_type(t, false);
+            &Type::Array(ref t, ref sz) => {
+                self.declarators.push(CDeclarator::Array(sz.clone()));
+                self.build_type(t, is_const, config);
             }
-            &Type::FuncPtr(ref ret, ref args) => {
-                let args = args.iter().map(|x| (None, CDecl::from_type(x))).collect();
-                self.declarators.push(CDeclarator::Ptr(false));
-                self.declarators.push(CDeclarator::Func(args));
-                self.build_type(ret, false);
+            &Type::FuncPtr(ref ret, ref args, variadic, abi, nullable) => {
+                let args = args.iter().map(|x| (None, CDecl::from_type(x, config))).collect();
+                let convention = config.function.calling_convention(abi).cloned();
+                self.declarators.push(CDeclarator::Ptr(false, nullable));
+                self.declarators.push(CDeclarator::Func(args, variadic, convention));
+                self.build_type(ret, false, config);
             }
         }
     }
 
-    fn to_string(&self, ident: Option<&str>) -> String
+    fn to_string(&self, ident: Option<&str>, config: &Config) -> String
     {
         // Build the left side (the type-specifier and type-qualifier),
         // and then build the right side (the declarators), and then
@@ -109,7 +127,14 @@ impl CDecl {
         let mut last_was_pointer = false;
         for declarator in &self.declarators {
             match declarator {
-                &CDeclarator::Ptr(ref is_const) => {
+                &CDeclarator::Ptr(ref is_const, ref nullable) => {
+                    match (&config.pointer.non_null_attribute, *nullable) {
+                        (&Some(ref attribute), false) => {
+                            left_declarators.insert_str(0, &format!("{} ", attribute));
+                        }
+                        _ => { }
+                    }
+
                     if *is_const {
                         left_declarators.insert_str(0, "*const ");
                     } else {
@@ -118,7 +143,7 @@ impl CDecl {
 
                     last_was_pointer = true;
                 },
-                &CDeclarator::Array(sz) => {
+                &CDeclarator::Array(ref sz) => {
                     if last_was_pointer {
                         left_declarators.insert_str(0, "(");
                         right_declarators.push_str(")");
@@ -127,7 +152,12 @@ impl CDecl {
 
                     last_was_pointer = false;
                 },
-                &CDeclarator::Func(ref args) => {
+                &CDeclarator::Func(ref args, variadic, ref convention) => {
+                    // The calling convention goes right before the name or
+                    // pointer, where compilers accept it in both C and C++
+                    if let &Some(ref convention) = convention {
+                        left_declarators.insert_str(0, &format!("{} ", convention));
+                    }
                     if last_was_pointer {
                         left_declarators.insert_str(0, "(");
                         right_declarators.push_str(")");
@@ -142,7 +172,13 @@ impl CDecl {
                         // This is gross, but needed to convert &Option<String> to Option<&str>
                         let arg_ident = arg_ident.as_ref().map(|x| x.as_ref());
 
-                        right_declarators.push_str(&arg_ty.to_string(arg_ident));
+                        right_declarators.push_str(&arg_ty.to_string(arg_ident, config));
+                    }
+                    if variadic {
+                        if !args.is_empty() {
+                            right_declarators.push_str(", ");
+                        }
+                        right_declarators.push_str("...");
                     }
                     right_declarators.push_str(")");
 
@@ -169,11 +205,24 @@ impl CDecl {
     }
 }
 
-pub fn write_func<F: Write>(out: &mut SourceWriter<F>, f: &Function)
+pub fn write_func<F: Write>(out: &mut SourceWriter<F>, config: &Config, f: &Function)
+{
+    out.write(&CDecl::from_func(f, config).to_string(Some(&f.name), config));
+}
+pub fn write_type<F: Write>(out: &mut SourceWriter<F>, config: &Config, t: &Type, ident: &str)
 {
-    out.write(&CDecl::from_func(f).to_string(Some(&f.name)));
+    out.write(&CDecl::from_type(t, config).to_string(Some(ident), config));
 }
-pub fn write_type<F: Write>(out: &mut SourceWriter<F>, t: &Type, ident: &str)
+/// Writes a type without a name, as in a template argument.
+pub fn write_abstract_type<F: Write>(out: &mut SourceWriter<F>, config: &Config, t: &Type)
 {
-    out.write(&CDecl::from_type(t).to_string(Some(ident)));
+    out.write(&CDecl::from_type(t, config).to_string(None, config));
+}
+pub fn write_static<F: Write>(out: &mut SourceWriter<F>, config: &Config, t: &Type, ident: &str, is_const: bool)
+{
+    if is_const {
+        out.write(&CDecl::from_const_type(t, config).to_string(Some(ident), config));
+    } else {
+        out.write(&CDecl::from_type(t, config).to_string(Some(ident), config));
+    }
 }
@@ -0,0 +1,183 @@
+use std::fmt;
+use std::io::Write;
+
+use syn::*;
+
+use bindgen::config::Config;
+use bindgen::writer::SourceWriter;
+
+/// A `#[cfg(..)]` predicate on an item, field or variant.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cfg {
+    /// A predicate like `windows` or `test`
+    Boolean(String),
+    /// A predicate like `feature = "gpu"`
+    Named(String, String),
+    Any(Vec<Cfg>),
+    All(Vec<Cfg>),
+    Not(Box<Cfg>),
+}
+
+impl fmt::Display for Cfg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Cfg::Boolean(ref key) => write!(f, "{}", key),
+            &Cfg::Named(ref key, ref value) => write!(f, "{} = \"{}\"", key, value),
+            &Cfg::Any(ref cfgs) | &Cfg::All(ref cfgs) => {
+                let name = if let &Cfg::Any(_) = self { "any" } else { "all" };
+                write!(f, "{}(", name)?;
+                for (i, cfg) in cfgs.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", cfg)?;
+                }
+                write!(f, ")")
+            }
+            &Cfg::Not(ref cfg) => write!(f, "not({})", cfg),
+        }
+    }
+}
+
+impl Cfg {
+    /// Parses the `#[cfg(..)]` attributes in a list of attributes. Items
+    /// with more than one are only compiled if all of them hold.
+    pub fn load(attrs: &[Attribute]) -> Option<Cfg> {
+        let mut cfgs = Vec::new();
+
+        for attr in attrs {
+            if attr.style != AttrStyle::Outer {
+                continue;
+            }
+            if let MetaItem::List(ref name, ref args) = attr.value {
+                if name != "cfg" || args.len() != 1 {
+                    continue;
+                }
+                match Cfg::load_nested(&args[0]) {
+                    Some(cfg) => cfgs.push(cfg),
+                    None => warn!("skipping unsupported cfg attribute on an item"),
+                }
+            }
+        }
+
+        match cfgs.len() {
+            0 => None,
+            1 => cfgs.pop(),
+            _ => Some(Cfg::All(cfgs)),
+        }
+    }
+
+    fn load_nested(item: &NestedMetaItem) -> Option<Cfg> {
+        match item {
+            &NestedMetaItem::MetaItem(MetaItem::Word(ref key)) => {
+                Some(Cfg::Boolean(key.to_string()))
+            }
+            &NestedMetaItem::MetaItem(MetaItem::NameValue(ref key, Lit::Str(ref value, _))) => {
+                Some(Cfg::Named(key.to_string(), value.clone()))
+            }
+            &NestedMetaItem::MetaItem(MetaItem::List(ref name, ref args)) => {
+                let mut cfgs = Vec::new();
+                for arg in args {
+                    cfgs.push(Cfg::load_nested(arg)?);
+                }
+
+                match name.as_ref() {
+                    "any" => Some(Cfg::Any(cfgs)),
+                    "all" => Some(Cfg::All(cfgs)),
+                    "not" if cfgs.len() == 1 => Some(Cfg::Not(Box::new(cfgs.pop().unwrap()))),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Combines the cfg of an item with the cfg of what contains it, such
+    /// as its module.
+    pub fn join(outer: &Option<Cfg>, inner: Option<Cfg>) -> Option<Cfg> {
+        match (outer, inner) {
+            (&None, inner) => inner,
+            (&Some(ref outer), None) => Some(outer.clone()),
+            (&Some(ref outer), Some(inner)) => {
+                if *outer == inner {
+                    return Some(inner);
+                }
+                Some(Cfg::All(vec![outer.clone(), inner]))
+            }
+        }
+    }
+
+    /// The preprocessor condition for this cfg, using the macros that
+    /// `defines` maps the predicates to. Returns the first predicate
+    /// without a macro if there is one.
+    pub fn condition(&self, config: &Config) -> Result<String, String> {
+        match self {
+            &Cfg::Boolean(..) | &Cfg::Named(..) => {
+                // Predicates are written as in rust, though `feature = gpu`
+                // is accepted for `feature = "gpu"` as well
+                let key = format!("{}", self);
+                let define = match self {
+                    &Cfg::Named(ref name, ref value) => {
+                        config.defines.get(&key)
+                                      .or_else(|| config.defines.get(&format!("{} = {}", name, value)))
+                    }
+                    _ => config.defines.get(&key),
+                };
+                match define {
+                    Some(define) => Ok(format!("defined({})", define)),
+                    None => Err(key),
+                }
+            }
+            &Cfg::Any(ref cfgs) | &Cfg::All(ref cfgs) => {
+                let op = if let &Cfg::Any(_) = self { " || " } else { " && " };
+                let conditions = cfgs.iter()
+                                     .map(|x| x.condition(config))
+                                     .collect::<Result<Vec<_>, _>>()?;
+
+                match conditions.len() {
+                    // `any()` is always false and `all()` is always true
+                    0 => Ok(String::from(if let &Cfg::Any(_) = self { "0" } else { "1" })),
+                    1 => Ok(conditions[0].clone()),
+                    _ => Ok(format!("({})", conditions.join(op))),
+                }
+            }
+            &Cfg::Not(ref cfg) => Ok(format!("!{}", cfg.condition(config)?)),
+        }
+    }
+}
+
+/// Writes the `#if` and `#endif` around a declaration with a cfg.
+pub trait ConditionWrite {
+    fn write_before<F: Write>(&self, config: &Config, out: &mut SourceWriter<F>);
+    fn write_after<F: Write>(&self, config: &Config, out: &mut SourceWriter<F>);
+}
+
+impl ConditionWrite for Option<Cfg> {
+    fn write_before<F: Write>(&self, config: &Config, out: &mut SourceWriter<F>) {
+        if let &Some(ref cfg) = self {
+            match cfg.condition(config) {
+                Ok(condition) => {
+                    out.write(&format!("#if {}", condition));
+                    out.new_line();
+                }
+                Err(key) => {
+                    warn!("no define for `{}` in the config, emitting `#[cfg({})]` unconditionally", key, cfg);
+                }
+            }
+        }
+    }
+
+    fn write_after<F: Write>(&self, config: &Config, out: &mut SourceWriter<F>) {
+        if let &Some(ref cfg) = self {
+            if cfg.condition(config).is_ok() {
+                out.new_line();
+                out.write("#endif");
+            }
+        }
+    }
+}
+
+/// An item that may only be compiled under a cfg.
+pub trait Conditional {
+    fn cfg(&self) -> &Option<Cfg>;
+}
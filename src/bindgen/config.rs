@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::default::Default;
 use std::fs::File;
 use std::io::prelude::*;
@@ -8,6 +9,8 @@ use std::str::FromStr;
 use toml;
 
 pub use bindgen::annotation::*;
+use bindgen::items::{Abi, PrimitiveType};
+use bindgen::utilities::c_string_literal;
 pub use bindgen::rename::*;
 
 pub const VERSION: &'static str = env!("CARGO_PKG_VERSION");
@@ -34,6 +37,37 @@ pub enum Layout {
     Auto,
 }
 
+/// A style of comment to write doc comments as.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DocumentationStyle {
+    /// `/** ... */`
+    Doxy,
+    /// `/// ...`
+    Cxx,
+    /// `// ...`
+    C99,
+}
+
+/// A way to expand the macros of a crate before parsing it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExpandStrategy {
+    /// `cargo rustc -- -Z unpretty=expanded`, which needs a nightly toolchain
+    Nightly,
+    /// The same command with `RUSTC_BOOTSTRAP=1` set, which makes a stable
+    /// toolchain accept the unstable option
+    Bootstrap,
+}
+
+/// The crates to parse after expanding their macros.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum ExpandConfig {
+    /// A list of crate names, which are expanded with a nightly toolchain
+    Crates(Vec<String>),
+    /// A table of crate names to the strategy to expand them with
+    Strategies(HashMap<String, ExpandStrategy>),
+}
+
 impl FromStr for Language {
     type Err = String;
 
@@ -82,9 +116,41 @@ impl FromStr for Layout {
     }
 }
 
+impl FromStr for DocumentationStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<DocumentationStyle, Self::Err> {
+        match s {
+            "Doxy" => Ok(DocumentationStyle::Doxy),
+            "doxy" => Ok(DocumentationStyle::Doxy),
+            "Cxx" => Ok(DocumentationStyle::Cxx),
+            "cxx" => Ok(DocumentationStyle::Cxx),
+            "C99" => Ok(DocumentationStyle::C99),
+            "c99" => Ok(DocumentationStyle::C99),
+            _ => Err(format!("unrecognized DocumentationStyle: '{}'", s)),
+        }
+    }
+}
+
+impl FromStr for ExpandStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ExpandStrategy, Self::Err> {
+        match s {
+            "Nightly" => Ok(ExpandStrategy::Nightly),
+            "nightly" => Ok(ExpandStrategy::Nightly),
+            "Bootstrap" => Ok(ExpandStrategy::Bootstrap),
+            "bootstrap" => Ok(ExpandStrategy::Bootstrap),
+            _ => Err(format!("unrecognized ExpandStrategy: '{}'", s)),
+        }
+    }
+}
+
 deserialize_enum_str!(Language);
 deserialize_enum_str!(Braces);
 deserialize_enum_str!(Layout);
+deserialize_enum_str!(DocumentationStyle);
+deserialize_enum_str!(ExpandStrategy);
 
 /// A collection of settings to customize the generated bindings.
 #[derive(Debug, Clone, Deserialize)]
@@ -110,8 +176,25 @@ pub struct Config {
     pub tab_width: usize,
     /// The language to output bindings for
     pub language: Language,
-    /// The names of crates to parse with `rustc --pretty=expanded`
-    pub expand: Vec<String>,
+    /// Whether to write the doc comments of items, fields and variants as
+    /// comments in the bindings
+    pub documentation: bool,
+    /// The style of comment to write doc comments as
+    pub documentation_style: DocumentationStyle,
+    /// Whether to convert the Markdown in doc comments, such as code spans,
+    /// links and `# Safety` sections, into Doxygen commands
+    pub documentation_doxygen: bool,
+    /// The crates to parse with their macros expanded by rustc, either as a
+    /// list of names or as a table of names to an `ExpandStrategy`
+    pub expand: ExpandConfig,
+    /// Additional headers to include, such as ones needed for the C types of `primitives`
+    pub includes: Vec<String>,
+    /// The C types to use for rust primitive types, overriding the defaults
+    pub primitives: HashMap<String, String>,
+    /// The macros to test in `#if` for `#[cfg]` predicates, keyed by the
+    /// predicate as it is written in rust, such as `windows` or
+    /// `feature = "gpu"`
+    pub defines: HashMap<String, String>,
     /// The configuration options for functions
     #[serde(rename = "fn")]
     pub function: FunctionConfig,
@@ -121,6 +204,14 @@ pub struct Config {
     /// The configuration options for enums
     #[serde(rename = "enum")]
     pub enumeration: EnumConfig,
+    /// The configuration options for constants
+    #[serde(rename = "const")]
+    pub constant: ConstantConfig,
+    /// The configuration options for `bitflags!` types
+    pub bitflags: BitflagsConfig,
+    /// The configuration options for pointers
+    #[serde(rename = "ptr")]
+    pub pointer: PtrConfig,
 }
 
 impl Default for Config {
@@ -135,10 +226,19 @@ impl Default for Config {
             line_length: 100,
             tab_width: 2,
             language: Language::Cxx,
-            expand: Vec::new(),
+            documentation: true,
+            documentation_style: DocumentationStyle::Doxy,
+            documentation_doxygen: false,
+            expand: ExpandConfig::Crates(Vec::new()),
+            includes: Vec::new(),
+            primitives: HashMap::new(),
+            defines: HashMap::new(),
             function: FunctionConfig::default(),
             structure: StructConfig::default(),
             enumeration: EnumConfig::default(),
+            constant: ConstantConfig::default(),
+            bitflags: BitflagsConfig::default(),
+            pointer: PtrConfig::default(),
         }
     }
 }
@@ -157,6 +257,22 @@ pub struct FunctionConfig {
     pub args: Layout,
     /// The rename rule to apply to function args
     pub rename_args: Option<RenameRule>,
+    /// The macros to place in the declarations of functions with each ABI,
+    /// such as `stdcall = "MYLIB_STDCALL"`
+    pub calling_conventions: HashMap<String, String>,
+    /// The attribute for functions returning `!`, such as `_Noreturn` or `[[noreturn]]`
+    pub no_return: Option<String>,
+    /// The attribute for `#[must_use]` functions, such as `[[nodiscard]]`
+    /// or `__attribute__((warn_unused_result))`
+    pub must_use: Option<String>,
+    /// The attribute for `#[cold]` functions, such as `__attribute__((cold))`
+    pub cold: Option<String>,
+    /// The attribute for `#[deprecated]` functions, such as `[[deprecated]]`
+    pub deprecated: Option<String>,
+    /// The attribute for `#[deprecated]` functions with a note, with
+    /// `{note}` replaced by the note as a string literal, such as
+    /// `[[deprecated({note})]]`. Defaults to `deprecated`
+    pub deprecated_with_note: Option<String>,
 }
 
 impl Default for FunctionConfig {
@@ -166,6 +282,12 @@ impl Default for FunctionConfig {
             postfix: None,
             args: Layout::Auto,
             rename_args: None,
+            calling_conventions: HashMap::new(),
+            no_return: None,
+            must_use: None,
+            cold: None,
+            deprecated: None,
+            deprecated_with_note: None,
         }
     }
 }
@@ -190,6 +312,30 @@ pub struct StructConfig {
     pub derive_gt: bool,
     /// Whether to generate a greater than or equal to operator on structs with one field
     pub derive_gte: bool,
+    /// Whether to generate `repr(transparent)` structs as a struct of their
+    /// one field, instead of a typedef of the field's type. This gives them a
+    /// distinct type, but may not be passed the same way as the field by
+    /// every ABI
+    pub transparent_as_struct: bool,
+    /// The attribute for `repr(packed)` structs, defaults to `__attribute__((packed))`
+    pub packed: Option<String>,
+    /// The attribute for `repr(align(N))` structs, with `{align}` replaced by
+    /// the alignment. Defaults to `alignas({align})` in C++ and
+    /// `_Alignas({align})` in C, where it is put on the first field
+    pub align: Option<String>,
+    /// The name of the structs generated for each use of a generic struct,
+    /// like `Foo<i32>`. `{name}` is replaced by the name of the struct and
+    /// `{args}` by its generic arguments, joined by `_`
+    pub generic_template: String,
+    /// Whether to write generic structs as C++ templates, and type aliases
+    /// of them as `using` declarations, instead of a struct for each use.
+    /// This has no effect on C
+    pub cxx_templates: bool,
+    /// The attribute for `#[deprecated]` structs, such as `[[deprecated]]`
+    pub deprecated: Option<String>,
+    /// The attribute for structs deprecated with a note. `{note}` is
+    /// replaced by the note as a string literal. Defaults to `deprecated`
+    pub deprecated_with_note: Option<String>,
 }
 
 impl Default for StructConfig {
@@ -202,6 +348,13 @@ impl Default for StructConfig {
             derive_lte: false,
             derive_gt: false,
             derive_gte: false,
+            transparent_as_struct: false,
+            packed: None,
+            align: None,
+            generic_template: String::from("{name}_{args}"),
+            cxx_templates: false,
+            deprecated: None,
+            deprecated_with_note: None,
         }
     }
 }
@@ -217,6 +370,11 @@ pub struct EnumConfig {
     /// Whether to add a `Sentinel` value at the end of every enum
     /// This is useful in Gecko for IPC serialization
     pub add_sentinel: bool,
+    /// The attribute for `#[deprecated]` enums, like `deprecated` of `[fn]`
+    pub deprecated: Option<String>,
+    /// The attribute for enums with a deprecation note, which replaces
+    /// `{note}`. If unset, `deprecated` is used
+    pub deprecated_with_note: Option<String>,
 }
 
 impl Default for EnumConfig {
@@ -224,6 +382,66 @@ impl Default for EnumConfig {
         EnumConfig {
             rename_variants: None,
             add_sentinel: false,
+            deprecated: None,
+            deprecated_with_note: None,
+        }
+    }
+}
+
+/// Settings to apply to generated constants.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(deny_unknown_fields)]
+#[serde(default)]
+pub struct ConstantConfig {
+    /// The rename rule to apply to the name of constants
+    pub rename_constants: Option<RenameRule>,
+    /// Whether to refer to exported constants by name in array lengths
+    pub use_in_array_lengths: bool,
+}
+
+impl Default for ConstantConfig {
+    fn default() -> ConstantConfig {
+        ConstantConfig {
+            rename_constants: None,
+            use_in_array_lengths: false,
+        }
+    }
+}
+
+/// Settings to apply to generated `bitflags!` types.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(deny_unknown_fields)]
+#[serde(default)]
+pub struct BitflagsConfig {
+    /// Whether to write bitflags as a struct with bitwise operators in C++,
+    /// instead of a typedef of their integer type
+    pub cxx_struct: bool,
+}
+
+impl Default for BitflagsConfig {
+    fn default() -> BitflagsConfig {
+        BitflagsConfig {
+            cxx_struct: false,
+        }
+    }
+}
+
+/// Settings to apply to generated pointers.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(deny_unknown_fields)]
+#[serde(default)]
+pub struct PtrConfig {
+    /// Optional attribute to apply to pointers that can't be null, such as `_Nonnull`
+    pub non_null_attribute: Option<String>,
+}
+
+impl Default for PtrConfig {
+    fn default() -> PtrConfig {
+        PtrConfig {
+            non_null_attribute: None,
         }
     }
 }
@@ -240,10 +458,24 @@ impl Config {
 
         let config_text = read(file_name).unwrap();
 
-        match toml::from_str::<Config>(&config_text) {
-            Ok(x) => Ok(x),
-            Err(e) => Err(format!("couldn't parse config file: {}", e)),
+        let config = match toml::from_str::<Config>(&config_text) {
+            Ok(x) => x,
+            Err(e) => return Err(format!("couldn't parse config file: {}", e)),
+        };
+
+        for name in config.primitives.keys() {
+            if PrimitiveType::maybe(name).is_none() {
+                return Err(format!("couldn't parse config file: unknown primitive type '{}'", name));
+            }
+        }
+
+        for name in config.function.calling_conventions.keys() {
+            if Abi::maybe(name).is_none() {
+                return Err(format!("couldn't parse config file: unknown ABI '{}'", name));
+            }
         }
+
+        Ok(config)
     }
 
     pub fn from_root_or_default(root: &str) -> Config {
@@ -255,6 +487,11 @@ impl Config {
             Config::default()
         }
     }
+
+    /// Whether generic structs are written as templates.
+    pub fn generics_as_templates(&self) -> bool {
+        self.language == Language::Cxx && self.structure.cxx_templates
+    }
 }
 
 impl FunctionConfig {
@@ -271,6 +508,50 @@ impl FunctionConfig {
         }
         self.postfix.clone()
     }
+
+    pub fn calling_convention(&self, abi: Abi) -> Option<&String> {
+        self.calling_conventions.get(abi.name())
+    }
+
+    pub fn deprecated_attribute(&self, note: &str) -> Option<String> {
+        deprecated_attribute(&self.deprecated, &self.deprecated_with_note, note)
+    }
+}
+
+/// The attribute for a deprecated item, where `note` is empty if the item
+/// doesn't have one.
+fn deprecated_attribute(deprecated: &Option<String>,
+                        deprecated_with_note: &Option<String>,
+                        note: &str) -> Option<String> {
+    match deprecated_with_note {
+        &Some(ref x) if !note.is_empty() => Some(x.replace("{note}", &c_string_literal(note))),
+        _ => deprecated.clone(),
+    }
+}
+
+impl ExpandConfig {
+    /// The strategy to expand a crate with, if it should be expanded.
+    pub fn strategy(&self, crate_name: &str) -> Option<ExpandStrategy> {
+        match self {
+            &ExpandConfig::Crates(ref crates) => {
+                if crates.iter().any(|x| x == crate_name) {
+                    Some(ExpandStrategy::Nightly)
+                } else {
+                    None
+                }
+            }
+            &ExpandConfig::Strategies(ref strategies) => strategies.get(crate_name).cloned(),
+        }
+    }
+}
+
+impl BitflagsConfig {
+    pub fn cxx_struct(&self, annotations: &AnnotationSet) -> bool {
+        if let Some(x) = annotations.bool("cxx-struct") {
+            return x;
+        }
+        self.cxx_struct
+    }
 }
 
 impl StructConfig {
@@ -310,6 +591,23 @@ impl StructConfig {
         }
         self.derive_gte
     }
+    pub fn deprecated_attribute(&self, note: &str) -> Option<String> {
+        deprecated_attribute(&self.deprecated, &self.deprecated_with_note, note)
+    }
+    pub fn packed_attribute(&self) -> String {
+        match self.packed {
+            Some(ref x) => x.clone(),
+            None => String::from("__attribute__((packed))"),
+        }
+    }
+    pub fn align_attribute(&self, language: &Language, align: u64) -> String {
+        let attribute = match (&self.align, language) {
+            (&Some(ref x), _) => x.as_str(),
+            (&None, &Language::Cxx) => "alignas({align})",
+            (&None, &Language::C) => "_Alignas({align})",
+        };
+        attribute.replace("{align}", &format!("{}", align))
+    }
 }
 
 impl EnumConfig {
@@ -319,4 +617,7 @@ impl EnumConfig {
         }
         self.add_sentinel
     }
+    pub fn deprecated_attribute(&self, note: &str) -> Option<String> {
+        deprecated_attribute(&self.deprecated, &self.deprecated_with_note, note)
+    }
 }
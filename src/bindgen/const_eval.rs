@@ -0,0 +1,381 @@
+use std::collections::{HashMap, HashSet};
+
+use quote::{ToTokens, Tokens};
+use syn;
+
+use bindgen::library::{ConvertResult, ModuleTree, PathRef};
+use bindgen::utilities::SynPathHelpers;
+
+// A small evaluator for the integer constant expressions that can appear
+// in enum discriminants, array lengths, and `const` items. Values are
+// computed as 128 bit integers so that every rust integer type fits, and
+// are wrapped to the type of the expression after every operation, when
+// it is known, so that `!0` and shifts behave as they do in rust.
+
+/// The values of integer `const` items that have been collected so far,
+/// keyed by their path.
+#[derive(Debug, Clone)]
+pub struct ConstantTable {
+    /// The paths of every integer constant, including the ones that
+    /// haven't been evaluated yet
+    declared: HashSet<PathRef>,
+    values: HashMap<PathRef, i128>,
+    /// The names of exported constants in the generated bindings, for
+    /// constants that can be referred to by name in array lengths
+    exported_names: HashMap<PathRef, String>,
+}
+
+impl ConstantTable {
+    pub fn new() -> ConstantTable {
+        ConstantTable {
+            declared: HashSet::new(),
+            values: HashMap::new(),
+            exported_names: HashMap::new(),
+        }
+    }
+
+    pub fn declare(&mut self, path: PathRef) {
+        self.declared.insert(path);
+    }
+
+    pub fn insert(&mut self, path: PathRef, value: i128) {
+        self.values.insert(path, value);
+    }
+
+    pub fn insert_exported_name(&mut self, path: PathRef, exported_name: String) {
+        self.exported_names.insert(path, exported_name);
+    }
+}
+
+/// The constants that can be named from inside of a module. Names are
+/// resolved through the module's imports, like the paths of types.
+#[derive(Debug, Clone)]
+pub struct ConstantScope<'a> {
+    table: &'a ConstantTable,
+    modules: &'a ModuleTree,
+    mod_path: PathRef,
+    /// Names that are only in scope for a single item, like the earlier
+    /// flags of a `bitflags!` type
+    locals: HashMap<String, i128>,
+}
+
+impl<'a> ConstantScope<'a> {
+    pub fn new(table: &'a ConstantTable, modules: &'a ModuleTree, mod_path: PathRef) -> ConstantScope<'a> {
+        ConstantScope {
+            table: table,
+            modules: modules,
+            mod_path: mod_path,
+            locals: HashMap::new(),
+        }
+    }
+
+    /// The path of the constant a path written in the module refers to.
+    fn resolve(&self, written: &str) -> Option<PathRef> {
+        let table = self.table;
+        self.modules.resolve(&self.mod_path, written, &|p| table.declared.contains(p))
+    }
+
+    pub fn get(&self, written: &str) -> Option<i128> {
+        if let Some(value) = self.locals.get(written) {
+            return Some(*value);
+        }
+        self.resolve(written).and_then(|path| self.table.values.get(&path).cloned())
+    }
+
+    pub fn insert_local(&mut self, name: String, value: i128) {
+        self.locals.insert(name, value);
+    }
+
+    /// Whether a path written in the module names a type of a parsed crate,
+    /// rather than one of std.
+    pub fn names_type(&self, written: &str) -> bool {
+        let modules = self.modules;
+        modules.resolve(&self.mod_path, written, &|p| modules.has_type(p)).is_some()
+    }
+
+    /// Whether a path written in the module names a type that is private to
+    /// another module.
+    pub fn names_private_type(&self, written: &str) -> bool {
+        let modules = self.modules;
+        modules.resolve(&self.mod_path, written, &|p| modules.has_type(p))
+               .map_or(false, |path| modules.has_private_type(&path))
+    }
+
+    pub fn exported_name(&self, written: &str) -> Option<&String> {
+        self.resolve(written).and_then(|path| self.table.exported_names.get(&path))
+    }
+}
+
+/// Evaluates a constant expression of the integer type `ty`, looking up
+/// paths in `constants`.
+pub fn evaluate(expr: &syn::ConstExpr,
+                ty: Option<&str>,
+                constants: &ConstantScope) -> ConvertResult<i128> {
+    evaluate_tree(&reassociate(expr), ty, constants)
+}
+
+fn evaluate_tree(expr: &syn::ConstExpr,
+                 ty: Option<&str>,
+                 constants: &ConstantScope) -> ConvertResult<i128> {
+    let value = match expr {
+        &syn::ConstExpr::Lit(ref lit) => {
+            evaluate_lit(lit)?
+        }
+        &syn::ConstExpr::Paren(ref inner) => {
+            evaluate_tree(inner, ty, constants)?
+        }
+        &syn::ConstExpr::Path(ref path) => {
+            evaluate_path(path, constants)?
+        }
+        &syn::ConstExpr::Unary(op, ref inner) => {
+            let value = evaluate_tree(inner, ty, constants)?;
+
+            match op {
+                syn::UnOp::Neg => value.checked_neg()
+                                       .ok_or_else(|| format!("overflow evaluating `{}`", to_string(expr)))?,
+                syn::UnOp::Not => !value,
+                syn::UnOp::Deref => return Err(format!("unsupported operator in `{}`", to_string(expr))),
+            }
+        }
+        &syn::ConstExpr::Binary(op, ref left, ref right) => {
+            let left = evaluate_tree(left, ty, constants)?;
+            let right = evaluate_tree(right, ty, constants)?;
+
+            let result = match op {
+                syn::BinOp::Add => left.checked_add(right),
+                syn::BinOp::Sub => left.checked_sub(right),
+                syn::BinOp::Mul => left.checked_mul(right),
+                syn::BinOp::Div => left.checked_div(right),
+                syn::BinOp::Rem => left.checked_rem(right),
+                syn::BinOp::BitXor => Some(left ^ right),
+                syn::BinOp::BitAnd => Some(left & right),
+                syn::BinOp::BitOr => Some(left | right),
+                syn::BinOp::Shl => {
+                    if right < 0 || right >= 128 { None } else { left.checked_shl(right as u32) }
+                }
+                syn::BinOp::Shr => {
+                    if right < 0 || right >= 128 { None } else { left.checked_shr(right as u32) }
+                }
+                _ => {
+                    return Err(format!("unsupported operator in `{}`", to_string(expr)));
+                }
+            };
+
+            result.ok_or_else(|| format!("overflow evaluating `{}`", to_string(expr)))?
+        }
+        &syn::ConstExpr::Cast(ref inner, ref cast_ty) => {
+            // The type of the casted expression is not known
+            let value = evaluate_tree(inner, None, constants)?;
+
+            match **cast_ty {
+                syn::Ty::Path(None, ref path) if path.segments.len() == 1 => {
+                    cast(value, &path.segments[0].ident.to_string())
+                        .ok_or_else(|| format!("unsupported cast in `{}`", to_string(expr)))?
+                }
+                _ => return Err(format!("unsupported cast in `{}`", to_string(expr))),
+            }
+        }
+        &syn::ConstExpr::Call(..) |
+        &syn::ConstExpr::Other(..) |
+        &syn::ConstExpr::Index(..) => {
+            return Err(format!("unsupported expression `{}`", to_string(expr)));
+        }
+    };
+
+    match ty {
+        Some(ty) => cast(value, ty).ok_or_else(|| format!("`{}` is not an integer type", ty)),
+        None => Ok(value),
+    }
+}
+
+/// Evaluates the expression of a `const` item of the integer type `ty`.
+pub fn evaluate_expr(expr: &syn::Expr,
+                     ty: Option<&str>,
+                     constants: &ConstantScope) -> ConvertResult<i128> {
+    match to_const_expr(expr) {
+        Some(converted) => evaluate(&converted, ty, constants),
+        None => Err(format!("unsupported expression `{}`", to_string(expr))),
+    }
+}
+
+/// Truncates or extends a value as rust would when casting to the integer
+/// type `ty`. Returns `None` if `ty` is not an integer type.
+pub fn cast(value: i128, ty: &str) -> Option<i128> {
+    let result = match ty {
+        "u8" | "c_uchar" => value as u8 as i128,
+        "u16" | "c_ushort" => value as u16 as i128,
+        "u32" | "c_uint" => value as u32 as i128,
+        "u64" | "usize" | "c_ulong" | "c_ulonglong" => value as u64 as i128,
+        "i8" | "c_char" | "c_schar" => value as i8 as i128,
+        "i16" | "c_short" => value as i16 as i128,
+        "i32" | "c_int" => value as i32 as i128,
+        "i64" | "isize" | "c_long" | "c_longlong" => value as i64 as i128,
+        "u128" | "i128" => value,
+        _ => return None,
+    };
+    Some(result)
+}
+
+fn evaluate_lit(lit: &syn::Lit) -> ConvertResult<i128> {
+    match lit {
+        &syn::Lit::Int(x, _) => Ok(x as i128),
+        &syn::Lit::Byte(x) => Ok(x as i128),
+        &syn::Lit::Char(x) => Ok(x as u32 as i128),
+        &syn::Lit::Bool(x) => Ok(x as i128),
+        _ => Err(format!("unsupported literal `{}`", to_string(lit))),
+    }
+}
+
+fn evaluate_path(path: &syn::Path, constants: &ConstantScope) -> ConvertResult<i128> {
+    if path.segments.iter().any(|x| !x.parameters.is_empty()) {
+        return Err(format!("unsupported path `{}`", to_string(path)));
+    }
+    let written = path.as_path_ref();
+
+    match constants.get(&written) {
+        Some(value) => Ok(value),
+        None => Err(format!("unknown constant `{}`", written)),
+    }
+}
+
+// syn parses operators without any precedence or associativity, so
+// `2 * 3 + 1` becomes `2 * (3 + 1)` and `-1 + 3` becomes `-(1 + 3)`.
+// Parentheses are kept as explicit nodes, so every chain of operators
+// outside of them is flattened back into source order and parsed again
+// with rust's precedence rules.
+
+enum Token {
+    Operand(syn::ConstExpr),
+    Unary(syn::UnOp),
+    Binary(syn::BinOp),
+    Cast(Box<syn::Ty>),
+}
+
+fn reassociate(expr: &syn::ConstExpr) -> syn::ConstExpr {
+    let mut tokens = Vec::new();
+    flatten(expr, &mut tokens);
+    tokens.reverse();
+    parse_binary(&mut tokens, 0)
+}
+
+fn flatten(expr: &syn::ConstExpr, tokens: &mut Vec<Token>) {
+    match expr {
+        &syn::ConstExpr::Binary(op, ref left, ref right) => {
+            flatten(left, tokens);
+            tokens.push(Token::Binary(op));
+            flatten(right, tokens);
+        }
+        &syn::ConstExpr::Unary(op, ref inner) => {
+            tokens.push(Token::Unary(op));
+            flatten(inner, tokens);
+        }
+        &syn::ConstExpr::Cast(ref inner, ref ty) => {
+            flatten(inner, tokens);
+            tokens.push(Token::Cast(ty.clone()));
+        }
+        &syn::ConstExpr::Paren(ref inner) => {
+            tokens.push(Token::Operand(syn::ConstExpr::Paren(Box::new(reassociate(inner)))));
+        }
+        &syn::ConstExpr::Other(ref other) => {
+            match to_const_expr(other) {
+                Some(converted) => flatten(&converted, tokens),
+                None => tokens.push(Token::Operand(expr.clone())),
+            }
+        }
+        _ => {
+            tokens.push(Token::Operand(expr.clone()));
+        }
+    }
+}
+
+fn precedence(op: syn::BinOp) -> usize {
+    match op {
+        syn::BinOp::Mul | syn::BinOp::Div | syn::BinOp::Rem => 10,
+        syn::BinOp::Add | syn::BinOp::Sub => 9,
+        syn::BinOp::Shl | syn::BinOp::Shr => 8,
+        syn::BinOp::BitAnd => 7,
+        syn::BinOp::BitXor => 6,
+        syn::BinOp::BitOr => 5,
+        syn::BinOp::Eq | syn::BinOp::Ne |
+        syn::BinOp::Lt | syn::BinOp::Le |
+        syn::BinOp::Gt | syn::BinOp::Ge => 4,
+        syn::BinOp::And => 3,
+        syn::BinOp::Or => 2,
+    }
+}
+
+/// Parses binary operators of at least `min_precedence` from a reversed
+/// token stream, treating every operator as left associative.
+fn parse_binary(tokens: &mut Vec<Token>, min_precedence: usize) -> syn::ConstExpr {
+    let mut left = parse_cast(tokens);
+
+    loop {
+        let op = match tokens.last() {
+            Some(&Token::Binary(op)) if precedence(op) >= min_precedence => op,
+            _ => break,
+        };
+        tokens.pop();
+
+        let right = parse_binary(tokens, precedence(op) + 1);
+        left = syn::ConstExpr::Binary(op, Box::new(left), Box::new(right));
+    }
+
+    left
+}
+
+/// Casts bind tighter than any binary operator, but looser than unary ones.
+fn parse_cast(tokens: &mut Vec<Token>) -> syn::ConstExpr {
+    let mut expr = parse_unary(tokens);
+
+    while let Some(&Token::Cast(_)) = tokens.last() {
+        if let Some(Token::Cast(ty)) = tokens.pop() {
+            expr = syn::ConstExpr::Cast(Box::new(expr), ty);
+        }
+    }
+
+    expr
+}
+
+fn parse_unary(tokens: &mut Vec<Token>) -> syn::ConstExpr {
+    match tokens.pop() {
+        Some(Token::Unary(op)) => syn::ConstExpr::Unary(op, Box::new(parse_unary(tokens))),
+        Some(Token::Operand(expr)) => expr,
+        // The tokens come from a valid expression, so this can't happen
+        _ => unreachable!(),
+    }
+}
+
+/// Converts the subset of expressions that can be evaluated into a
+/// `ConstExpr`, or returns `None` if the expression can't be evaluated.
+fn to_const_expr(expr: &syn::Expr) -> Option<syn::ConstExpr> {
+    let converted = match expr.node {
+        syn::ExprKind::Lit(ref lit) => {
+            syn::ConstExpr::Lit(lit.clone())
+        }
+        syn::ExprKind::Paren(ref inner) => {
+            syn::ConstExpr::Paren(Box::new(to_const_expr(inner)?))
+        }
+        syn::ExprKind::Path(None, ref path) => {
+            syn::ConstExpr::Path(path.clone())
+        }
+        syn::ExprKind::Unary(op, ref inner) => {
+            syn::ConstExpr::Unary(op, Box::new(to_const_expr(inner)?))
+        }
+        syn::ExprKind::Binary(op, ref left, ref right) => {
+            syn::ConstExpr::Binary(op,
+                                   Box::new(to_const_expr(left)?),
+                                   Box::new(to_const_expr(right)?))
+        }
+        syn::ExprKind::Cast(ref inner, ref ty) => {
+            syn::ConstExpr::Cast(Box::new(to_const_expr(inner)?), ty.clone())
+        }
+        _ => return None,
+    };
+    Some(converted)
+}
+
+fn to_string<T: ToTokens>(x: &T) -> String {
+    let mut tokens = Tokens::new();
+    x.to_tokens(&mut tokens);
+    tokens.as_str().to_owned()
+}
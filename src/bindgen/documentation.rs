@@ -0,0 +1,260 @@
+use std::io::Write;
+
+use syn::*;
+
+use bindgen::config::{Config, DocumentationStyle};
+use bindgen::writer::{Source, SourceWriter};
+
+/// The doc comment of an item, field or variant, without the lines that
+/// are annotations.
+#[derive(Debug, Clone)]
+pub struct Documentation {
+    pub lines: Vec<String>,
+}
+
+impl Documentation {
+    pub fn none() -> Documentation {
+        Documentation {
+            lines: Vec::new(),
+        }
+    }
+
+    /// Collects the `///`, `/** */` and `#[doc = ".."]` attributes in a list
+    /// of attributes.
+    pub fn load(attrs: &[Attribute]) -> Documentation {
+        let mut lines = Vec::new();
+
+        for attr in attrs {
+            if attr.style != AttrStyle::Outer {
+                continue;
+            }
+            let text = match attr.value {
+                MetaItem::NameValue(ref name, Lit::Str(ref text, _)) if name == "doc" => text,
+                _ => continue,
+            };
+
+            if !attr.is_sugared_doc {
+                lines.extend(text.lines().map(|x| strip_space(x).to_owned()));
+            } else if text.starts_with("///") {
+                lines.push(strip_space(&text[3..]).to_owned());
+            } else if text.starts_with("/**") && text.ends_with("*/") && text.len() >= 5 {
+                // Block comments are commonly written with a `*` at the
+                // start of each line
+                for line in text[3..text.len() - 2].lines() {
+                    let trimmed = line.trim_start();
+                    if trimmed.starts_with("*") {
+                        lines.push(strip_space(&trimmed[1..]).to_owned());
+                    } else {
+                        lines.push(strip_space(line).to_owned());
+                    }
+                }
+            }
+        }
+
+        lines.retain(|x| !x.trim().starts_with("cbindgen:"));
+
+        while lines.first().map_or(false, |x| x.trim().is_empty()) {
+            lines.remove(0);
+        }
+        while lines.last().map_or(false, |x| x.trim().is_empty()) {
+            lines.pop();
+        }
+
+        Documentation {
+            lines: lines,
+        }
+    }
+}
+
+impl Source for Documentation {
+    fn write<F: Write>(&self, config: &Config, out: &mut SourceWriter<F>) {
+        if self.lines.is_empty() || !config.documentation {
+            return;
+        }
+
+        let lines = if config.documentation_doxygen {
+            markdown_to_doxygen(&self.lines)
+        } else {
+            self.lines.clone()
+        };
+
+        let prefix = match config.documentation_style {
+            DocumentationStyle::Doxy => {
+                out.write("/**");
+                out.new_line();
+                " *"
+            }
+            DocumentationStyle::Cxx => "///",
+            DocumentationStyle::C99 => "//",
+        };
+
+        for line in &lines {
+            // A `*/` would end a block comment early, and a `\` at the end
+            // of a line comment would continue it onto the next line
+            let line = if config.documentation_style == DocumentationStyle::Doxy {
+                line.replace("*/", "* /")
+            } else {
+                line.trim_end_matches('\\').to_owned()
+            };
+
+            if line.is_empty() {
+                out.write(prefix);
+            } else {
+                out.write(&format!("{} {}", prefix, line));
+            }
+            out.new_line();
+        }
+
+        if config.documentation_style == DocumentationStyle::Doxy {
+            out.write(" */");
+            out.new_line();
+        }
+    }
+}
+
+/// Removes the one space that usually follows the start of a doc comment,
+/// keeping any further indentation.
+fn strip_space(line: &str) -> &str {
+    let line = line.trim_end();
+    if line.starts_with(" ") {
+        &line[1..]
+    } else {
+        line
+    }
+}
+
+/// Converts the Markdown that is common in rustdoc into Doxygen commands:
+/// headings, code blocks, code spans and links.
+fn markdown_to_doxygen(lines: &[String]) -> Vec<String> {
+    let mut result = Vec::new();
+    // Whether we are in a code block, and whether it is Rust code
+    let mut code_block = None;
+    // Whether the last line was a heading, which starts a paragraph
+    let mut after_heading = false;
+
+    for line in lines {
+        let trimmed = line.trim();
+
+        if after_heading && trimmed.is_empty() {
+            continue;
+        }
+        after_heading = false;
+
+        if trimmed.starts_with("```") {
+            match code_block {
+                Some(_) => {
+                    result.push(String::from("\\endcode"));
+                    code_block = None;
+                }
+                None => {
+                    // Code blocks are Rust unless another language is given
+                    let lang = trimmed[3..].trim();
+                    let is_rust = lang.is_empty() || lang.split(',').any(|x| x.trim() == "rust");
+                    result.push(String::from("\\code"));
+                    code_block = Some(is_rust);
+                }
+            }
+            continue;
+        }
+
+        match code_block {
+            Some(true) => {
+                // Lines starting with `#` are hidden from Rust examples
+                if trimmed != "#" && !trimmed.starts_with("# ") {
+                    result.push(line.clone());
+                }
+            }
+            Some(false) => result.push(line.clone()),
+            None => {
+                if trimmed.starts_with("#") {
+                    let heading = trimmed.trim_start_matches('#').trim();
+                    if heading == "Safety" {
+                        result.push(String::from("\\warning"));
+                    } else {
+                        result.push(format!("\\par {}", heading));
+                    }
+                    after_heading = true;
+                } else {
+                    result.push(convert_inline(line));
+                }
+            }
+        }
+    }
+
+    if code_block.is_some() {
+        result.push(String::from("\\endcode"));
+    }
+
+    result
+}
+
+/// Converts the code spans and links in a line of Markdown.
+fn convert_inline(line: &str) -> String {
+    let chars = line.chars().collect::<Vec<_>>();
+    let mut result = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '`' => {
+                if let Some(len) = chars[i + 1..].iter().position(|x| *x == '`') {
+                    let code = chars[i + 1..i + 1 + len].iter().cloned().collect::<String>();
+                    result.push_str(&code_span(&code));
+                    i += len + 2;
+                    continue;
+                }
+            }
+            '[' => {
+                if let Some(len) = chars[i + 1..].iter().position(|x| *x == ']') {
+                    let text = chars[i + 1..i + 1 + len].iter().cloned().collect::<String>();
+                    let after = i + len + 2;
+
+                    // `[text](url)` is an external link, and `[text]` with
+                    // nothing after it is a link to another item
+                    if chars.get(after) == Some(&'(') {
+                        if let Some(url_len) = chars[after + 1..].iter().position(|x| *x == ')') {
+                            let url = chars[after + 1..after + 1 + url_len].iter().cloned().collect::<String>();
+                            if url.contains("://") {
+                                result.push_str(&format!("<a href=\"{}\">{}</a>", url, convert_inline(&text)));
+                            } else {
+                                result.push_str(&convert_inline(&text));
+                            }
+                            i = after + url_len + 2;
+                            continue;
+                        }
+                    } else if chars.get(after) != Some(&'[') && is_item_link(&text) {
+                        result.push_str(&convert_inline(&text));
+                        i = after;
+                        continue;
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        result.push(chars[i]);
+        i += 1;
+    }
+
+    result
+}
+
+/// Whether the text of a `[text]` is the name or path of an item, rather
+/// than something like an interval.
+fn is_item_link(text: &str) -> bool {
+    let name = text.trim_matches('`');
+    match name.chars().next() {
+        Some(c) if c.is_alphabetic() || c == '_' => {
+            name.chars().all(|x| x.is_alphanumeric() || x == '_' || x == ':')
+        }
+        _ => false,
+    }
+}
+
+fn code_span(code: &str) -> String {
+    if !code.is_empty() && code.chars().all(|x| x.is_alphanumeric() || x == '_') {
+        format!("\\c {}", code)
+    } else {
+        format!("<tt>{}</tt>", code)
+    }
+}
@@ -4,8 +4,12 @@ use std::fmt;
 use syn;
 
 use bindgen::cdecl;
+use bindgen::cfg::*;
 use bindgen::config::{Config, Language, Layout};
 use bindgen::annotation::*;
+use bindgen::const_eval;
+use bindgen::const_eval::ConstantScope;
+use bindgen::documentation::Documentation;
 use bindgen::library::*;
 use bindgen::rename::*;
 use bindgen::utilities::*;
@@ -19,6 +23,7 @@ pub enum PrimitiveType {
     WChar,
     SChar,
     UChar,
+    Char32,
     Short,
     Int,
     Long,
@@ -28,19 +33,22 @@ pub enum PrimitiveType {
     ULong,
     ULongLong,
     USize,
+    ISize,
     UInt8,
     UInt16,
     UInt32,
     UInt64,
+    UInt128,
     Int8,
     Int16,
     Int32,
     Int64,
+    Int128,
     Float,
     Double,
 }
 impl PrimitiveType {
-    fn maybe(path: &str) -> Option<PrimitiveType> {
+    pub fn maybe(path: &str) -> Option<PrimitiveType> {
         match path {
             "c_void" => Some(PrimitiveType::Void),
             "c_char" => Some(PrimitiveType::Char),
@@ -56,23 +64,83 @@ impl PrimitiveType {
             "c_uint" => Some(PrimitiveType::UInt),
             "c_ulong" => Some(PrimitiveType::ULong),
             "c_ulonglong" => Some(PrimitiveType::ULongLong),
+            "wchar_t" => Some(PrimitiveType::WChar),
             "bool" => Some(PrimitiveType::Bool),
-            "char" => Some(PrimitiveType::WChar),
+            "char" => Some(PrimitiveType::Char32),
             "usize" => Some(PrimitiveType::USize),
+            "isize" => Some(PrimitiveType::ISize),
             "u8" => Some(PrimitiveType::UInt8),
             "u16" => Some(PrimitiveType::UInt16),
             "u32" => Some(PrimitiveType::UInt32),
             "u64" => Some(PrimitiveType::UInt64),
+            "u128" => Some(PrimitiveType::UInt128),
             "i8" => Some(PrimitiveType::Int8),
             "i16" => Some(PrimitiveType::Int16),
             "i32" => Some(PrimitiveType::Int32),
             "i64" => Some(PrimitiveType::Int64),
+            "i128" => Some(PrimitiveType::Int128),
             "f32" => Some(PrimitiveType::Float),
             "f64" => Some(PrimitiveType::Double),
+            // The `NonZero` integers have the same layout as the integer
+            "NonZeroUsize" => Some(PrimitiveType::USize),
+            "NonZeroIsize" => Some(PrimitiveType::ISize),
+            "NonZeroU8" => Some(PrimitiveType::UInt8),
+            "NonZeroU16" => Some(PrimitiveType::UInt16),
+            "NonZeroU32" => Some(PrimitiveType::UInt32),
+            "NonZeroU64" => Some(PrimitiveType::UInt64),
+            "NonZeroU128" => Some(PrimitiveType::UInt128),
+            "NonZeroI8" => Some(PrimitiveType::Int8),
+            "NonZeroI16" => Some(PrimitiveType::Int16),
+            "NonZeroI32" => Some(PrimitiveType::Int32),
+            "NonZeroI64" => Some(PrimitiveType::Int64),
+            "NonZeroI128" => Some(PrimitiveType::Int128),
             _ => None,
         }
     }
 
+    /// The name of the rust type, used for overriding the C type in the config.
+    pub fn rust_name(&self) -> &'static str {
+        match self {
+            &PrimitiveType::Void => "c_void",
+            &PrimitiveType::Bool => "bool",
+            &PrimitiveType::Char => "c_char",
+            &PrimitiveType::WChar => "wchar_t",
+            &PrimitiveType::SChar => "c_schar",
+            &PrimitiveType::UChar => "c_uchar",
+            &PrimitiveType::Char32 => "char",
+            &PrimitiveType::Short => "c_short",
+            &PrimitiveType::Int => "c_int",
+            &PrimitiveType::Long => "c_long",
+            &PrimitiveType::LongLong => "c_longlong",
+            &PrimitiveType::UShort => "c_ushort",
+            &PrimitiveType::UInt => "c_uint",
+            &PrimitiveType::ULong => "c_ulong",
+            &PrimitiveType::ULongLong => "c_ulonglong",
+            &PrimitiveType::USize => "usize",
+            &PrimitiveType::ISize => "isize",
+            &PrimitiveType::UInt8 => "u8",
+            &PrimitiveType::UInt16 => "u16",
+            &PrimitiveType::UInt32 => "u32",
+            &PrimitiveType::UInt64 => "u64",
+            &PrimitiveType::UInt128 => "u128",
+            &PrimitiveType::Int8 => "i8",
+            &PrimitiveType::Int16 => "i16",
+            &PrimitiveType::Int32 => "i32",
+            &PrimitiveType::Int64 => "i64",
+            &PrimitiveType::Int128 => "i128",
+            &PrimitiveType::Float => "f32",
+            &PrimitiveType::Double => "f64",
+        }
+    }
+
+    /// The C type to write, which can be overridden in the config.
+    pub fn c_name(&self, config: &Config) -> String {
+        match config.primitives.get(self.rust_name()) {
+            Some(name) => name.clone(),
+            None => self.to_string(),
+        }
+    }
+
     fn can_cmp_order(&self) -> bool {
         match self {
             &PrimitiveType::Bool => false,
@@ -92,6 +160,7 @@ impl fmt::Display for PrimitiveType {
             &PrimitiveType::WChar => write!(f, "wchar_t"),
             &PrimitiveType::SChar => write!(f, "signed char"),
             &PrimitiveType::UChar => write!(f, "unsigned char"),
+            &PrimitiveType::Char32 => write!(f, "uint32_t"),
             &PrimitiveType::Short => write!(f, "short"),
             &PrimitiveType::Int => write!(f, "int"),
             &PrimitiveType::Long => write!(f, "long"),
@@ -101,14 +170,17 @@ impl fmt::Display for PrimitiveType {
             &PrimitiveType::ULong => write!(f, "unsigned long"),
             &PrimitiveType::ULongLong => write!(f, "unsigned long long"),
             &PrimitiveType::USize => write!(f, "size_t"),
+            &PrimitiveType::ISize => write!(f, "ptrdiff_t"),
             &PrimitiveType::UInt8 => write!(f, "uint8_t"),
             &PrimitiveType::UInt16 => write!(f, "uint16_t"),
             &PrimitiveType::UInt32 => write!(f, "uint32_t"),
             &PrimitiveType::UInt64 => write!(f, "uint64_t"),
+            &PrimitiveType::UInt128 => write!(f, "unsigned __int128"),
             &PrimitiveType::Int8 => write!(f, "int8_t"),
             &PrimitiveType::Int16 => write!(f, "int16_t"),
             &PrimitiveType::Int32 => write!(f, "int32_t"),
             &PrimitiveType::Int64 => write!(f, "int64_t"),
+            &PrimitiveType::Int128 => write!(f, "__int128"),
             &PrimitiveType::Float => write!(f, "float"),
             &PrimitiveType::Double => write!(f, "double")
         }
@@ -117,18 +189,24 @@ impl fmt::Display for PrimitiveType {
 
 #[derive(Debug, Clone)]
 pub enum Type {
-    ConstPtr(Box<Type>),
-    Ptr(Box<Type>),
-    Path(PathRef),
+    /// A pointer to a const value, and whether it can be null
+    ConstPtr(Box<Type>, bool),
+    /// A pointer to a mutable value, and whether it can be null
+    Ptr(Box<Type>, bool),
+    /// A path to an item, and its generic arguments
+    Path(PathRef, Vec<Type>),
     Primitive(PrimitiveType),
-    Array(Box<Type>, u64),
-    FuncPtr(Box<Type>, Vec<Type>),
+    /// An array with its length, as it should be written in C
+    Array(Box<Type>, String),
+    /// A function pointer with its return type, its args and whether it is
+    /// variadic, its calling convention, and whether it can be null
+    FuncPtr(Box<Type>, Vec<Type>, bool, Abi, bool),
 }
 impl Type {
-    pub fn convert(ty: &syn::Ty) -> ConvertResult<Option<Type>> {
+    pub fn convert(ty: &syn::Ty, constants: &ConstantScope) -> ConvertResult<Option<Type>> {
         let converted = match ty {
             &syn::Ty::Rptr(_, ref mut_ty) => {
-                let converted = try!(Type::convert(&mut_ty.ty));
+                let converted = try!(Type::convert(&mut_ty.ty, constants));
 
                 let converted = match converted {
                     Some(converted) => converted,
@@ -136,12 +214,12 @@ impl Type {
                 };
 
                 match mut_ty.mutability {
-                    syn::Mutability::Mutable => Type::Ptr(Box::new(converted)),
-                    syn::Mutability::Immutable => Type::ConstPtr(Box::new(converted)),
+                    syn::Mutability::Mutable => Type::Ptr(Box::new(converted), false),
+                    syn::Mutability::Immutable => Type::ConstPtr(Box::new(converted), false),
                 }
             }
             &syn::Ty::Ptr(ref mut_ty) => {
-                let converted = try!(Type::convert(&mut_ty.ty));
+                let converted = try!(Type::convert(&mut_ty.ty, constants));
 
                 let converted = match converted {
                     Some(converted) => converted,
@@ -149,41 +227,66 @@ impl Type {
                 };
 
                 match mut_ty.mutability {
-                    syn::Mutability::Mutable => Type::Ptr(Box::new(converted)),
-                    syn::Mutability::Immutable => Type::ConstPtr(Box::new(converted)),
+                    syn::Mutability::Mutable => Type::Ptr(Box::new(converted), true),
+                    syn::Mutability::Immutable => Type::ConstPtr(Box::new(converted), true),
                 }
             }
             &syn::Ty::Path(_, ref p) => {
-                let (name, generics) = try!(p.convert_to_generic_single_segment());
+                let (path, generics) = try!(p.convert_to_generic_path(constants));
+                let name = path_name(&path);
 
                 if name == "PhantomData" {
                     return Ok(None);
-                } else if generics.len() != 0 {
-                    return Err(format!("cannot have a type with generics"));
-                } else {
-                    if let Some(prim) = PrimitiveType::maybe(&name) {
-                        Type::Primitive(prim)
-                    } else {
-                        Type::Path(name)
+                }
+                // A type of the crate can have the name of a std wrapper
+                if !constants.names_type(&p.as_path_ref()) {
+                    if let Some(wrapper) = Type::convert_std_wrapper(p, generics.clone()) {
+                        return wrapper.map(Some);
                     }
                 }
+
+                match PrimitiveType::maybe(name) {
+                    Some(prim) if p.is_c_type_path() && generics.is_empty() => Type::Primitive(prim),
+                    _ => Type::Path(path, generics),
+                }
             }
-            &syn::Ty::Array(ref ty, syn::ConstExpr::Lit(syn::Lit::Int(sz, _))) => {
-                let converted = try!(Type::convert(ty));
+            &syn::Ty::Array(ref ty, ref len) => {
+                let converted = try!(Type::convert(ty, constants));
 
                 let converted = match converted {
                     Some(converted) => converted,
                     None => return Err(format!("cannot have an array of zero sized types")),
                 };
 
-                Type::Array(Box::new(converted), sz)
+                let value = const_eval::evaluate(len, Some("usize"), constants)
+                                       .map_err(|msg| format!("unsupported array length - ({})", msg))?;
+
+                // Lengths given by an exported constant can refer to it
+                let sz = match len {
+                    &syn::ConstExpr::Path(ref p) => {
+                        constants.exported_name(&p.as_path_ref()).cloned()
+                    }
+                    _ => None,
+                };
+
+                Type::Array(Box::new(converted), sz.unwrap_or_else(|| format!("{}", value)))
             },
             &syn::Ty::BareFn(ref f) => {
                 let args = try!(f.inputs.iter()
-                                        .try_skip_map(|x| Type::convert(&x.ty)));
-                let ret = try!(f.output.as_type());
+                                        .try_skip_map(|x| Type::convert(&x.ty, constants)));
+                let ret = try!(f.output.as_type(constants));
+
+                // A function pointer without an ABI is a rust function, which
+                // is taken to be a C one
+                let abi = match f.abi {
+                    Some(ref abi) => {
+                        abi.c_abi()
+                           .ok_or_else(|| format!("function pointers with a non C ABI are not supported"))?
+                    }
+                    None => Abi::C,
+                };
 
-                Type::FuncPtr(Box::new(ret), args)
+                Type::FuncPtr(Box::new(ret), args, f.variadic, abi, false)
             },
             &syn::Ty::Tup(ref tys) => {
                 if tys.len() == 0 {
@@ -197,28 +300,79 @@ impl Type {
         return Ok(Some(converted));
     }
 
+    /// Converts the std types that are guaranteed to have the layout of a
+    /// pointer, or of a nullable pointer when wrapped in an `Option`.
+    fn convert_std_wrapper(path: &syn::Path, generics: Vec<Type>) -> Option<ConvertResult<Type>> {
+        let name = match path.segments.last() {
+            Some(last) => last.ident.to_string(),
+            None => return None,
+        };
+
+        let is_std = path.segments.len() == 1 ||
+                     ["std", "core", "alloc"].iter().any(|x| path.segments[0].ident == *x);
+        if !is_std {
+            return None;
+        }
+
+        let mut generics = generics.into_iter();
+        let inner = match (generics.next(), generics.next()) {
+            (Some(inner), None) => inner,
+            _ => return None,
+        };
+
+        match name.as_ref() {
+            "Box" | "NonNull" => Some(Ok(Type::Ptr(Box::new(inner), false))),
+            "Option" => {
+                Some(match inner {
+                    Type::ConstPtr(t, false) => Ok(Type::ConstPtr(t, true)),
+                    Type::Ptr(t, false) => Ok(Type::Ptr(t, true)),
+                    Type::FuncPtr(ret, args, variadic, abi, false) => {
+                        Ok(Type::FuncPtr(ret, args, variadic, abi, true))
+                    }
+                    _ => Err(format!("Option is only supported around references, Box, NonNull and fn")),
+                })
+            }
+            _ => None,
+        }
+    }
+
     pub fn add_deps_with_generics(&self, generic_params: &Vec<String>, library: &Library, out: &mut DependencyGraph) {
         match self {
-            &Type::ConstPtr(ref t) => {
-                t.add_deps(library, out);
+            &Type::ConstPtr(ref t, _) => {
+                t.add_deps_with_generics(generic_params, library, out);
             }
-            &Type::Ptr(ref t) => {
-                t.add_deps(library, out);
+            &Type::Ptr(ref t, _) => {
+                t.add_deps_with_generics(generic_params, library, out);
             }
-            &Type::Path(ref p) => {
-                if generic_params.contains(p) {
+            &Type::Path(ref p, ref generics) => {
+                if generics.is_empty() {
+                    if !generic_params.contains(p) {
+                        library.add_deps_for_path(p, out);
+                    }
                     return;
                 }
-                library.add_deps_for_path(p, out);
+
+                for generic in generics {
+                    generic.add_deps_with_generics(generic_params, library, out);
+                }
+
+                // A template is written once for every use, while other uses of
+                // generic params, like `Foo<T>` in a generic struct, are only
+                // instantiated once the params have values
+                if library.config().generics_as_templates() {
+                    library.add_deps_for_path(p, out);
+                } else if !generics.iter().any(|x| x.has_generic_params(generic_params)) {
+                    library.add_deps_for_generic_path(p, generics, out);
+                }
             }
             &Type::Primitive(_) => { }
             &Type::Array(ref t, _) => {
-                t.add_deps(library, out);
+                t.add_deps_with_generics(generic_params, library, out);
             }
-            &Type::FuncPtr(ref ret, ref args) => {
-                ret.add_deps(library, out);
+            &Type::FuncPtr(ref ret, ref args, ..) => {
+                ret.add_deps_with_generics(generic_params, library, out);
                 for arg in args {
-                    arg.add_deps(library, out);
+                    arg.add_deps_with_generics(generic_params, library, out);
                 }
             }
         }
@@ -228,38 +382,130 @@ impl Type {
         self.add_deps_with_generics(&Vec::new(), library, out)
     }
 
+    /// Whether this type refers to any of `generic_params`.
+    fn has_generic_params(&self, generic_params: &Vec<String>) -> bool {
+        match self {
+            &Type::ConstPtr(ref t, _) => t.has_generic_params(generic_params),
+            &Type::Ptr(ref t, _) => t.has_generic_params(generic_params),
+            &Type::Path(ref p, ref generics) => {
+                (generics.is_empty() && generic_params.contains(p)) ||
+                generics.iter().any(|x| x.has_generic_params(generic_params))
+            }
+            &Type::Primitive(_) => false,
+            &Type::Array(ref t, _) => t.has_generic_params(generic_params),
+            &Type::FuncPtr(ref ret, ref args, ..) => {
+                ret.has_generic_params(generic_params) ||
+                args.iter().any(|x| x.has_generic_params(generic_params))
+            }
+        }
+    }
+
+    /// The part of a mangled name for this type, when it is used as a
+    /// generic argument.
+    pub fn mangle(&self, config: &Config) -> String {
+        match self {
+            &Type::ConstPtr(ref t, _) => format!("ConstPtr_{}", t.mangle(config)),
+            &Type::Ptr(ref t, _) => format!("Ptr_{}", t.mangle(config)),
+            &Type::Path(ref p, ref generics) => mangle_path(p, generics, config),
+            &Type::Primitive(ref p) => String::from(p.rust_name()),
+            &Type::Array(ref t, ref sz) => format!("Array_{}_{}", t.mangle(config), sz),
+            &Type::FuncPtr(ref ret, ref args, ..) => {
+                let mut name = String::from("Fn");
+                for arg in args {
+                    name.push('_');
+                    name.push_str(&arg.mangle(config));
+                }
+                format!("{}_Ret_{}", name, ret.mangle(config))
+            }
+        }
+    }
+
     pub fn specialize(&self, mappings: &Vec<(&String, &Type)>) -> Type {
         match self {
-            &Type::ConstPtr(ref t) => {
-                Type::ConstPtr(Box::new(t.specialize(mappings)))
+            &Type::ConstPtr(ref t, nullable) => {
+                Type::ConstPtr(Box::new(t.specialize(mappings)), nullable)
             }
-            &Type::Ptr(ref t) => {
-                Type::Ptr(Box::new(t.specialize(mappings)))
+            &Type::Ptr(ref t, nullable) => {
+                Type::Ptr(Box::new(t.specialize(mappings)), nullable)
             }
-            &Type::Path(ref p) => {
-                for &(param, value) in mappings {
-                    if *p == *param {
-                        return value.clone();
+            &Type::Path(ref p, ref generics) => {
+                if generics.is_empty() {
+                    for &(param, value) in mappings {
+                        if *p == *param {
+                            return value.clone();
+                        }
                     }
                 }
 
-                Type::Path(p.clone())
+                Type::Path(p.clone(),
+                           generics.iter()
+                                   .map(|x| x.specialize(mappings))
+                                   .collect())
             }
             &Type::Primitive(ref p) => {
                 Type::Primitive(p.clone())
             }
             &Type::Array(ref t, ref sz) => {
-                Type::Array(Box::new(t.specialize(mappings)), *sz)
+                Type::Array(Box::new(t.specialize(mappings)), sz.clone())
             }
-            &Type::FuncPtr(ref ret, ref args) => {
+            &Type::FuncPtr(ref ret, ref args, variadic, abi, nullable) => {
                 Type::FuncPtr(Box::new(ret.specialize(mappings)),
                               args.iter()
                                   .map(|x| x.specialize(mappings))
-                                  .collect())
+                                  .collect(),
+                              variadic,
+                              abi,
+                              nullable)
+            }
+        }
+    }
+
+    /// Rewrites every path in this type with `f`, leaving generic params as they are.
+    pub fn map_paths_with_generics<F>(&self, generic_params: &Vec<PathRef>, f: &F) -> Type
+        where F: Fn(&PathRef) -> PathRef
+    {
+        match self {
+            &Type::ConstPtr(ref t, nullable) => {
+                Type::ConstPtr(Box::new(t.map_paths_with_generics(generic_params, f)), nullable)
+            }
+            &Type::Ptr(ref t, nullable) => {
+                Type::Ptr(Box::new(t.map_paths_with_generics(generic_params, f)), nullable)
+            }
+            &Type::Path(ref p, ref generics) => {
+                let generics = generics.iter()
+                                       .map(|x| x.map_paths_with_generics(generic_params, f))
+                                       .collect::<Vec<_>>();
+
+                if generics.is_empty() && generic_params.contains(p) {
+                    Type::Path(p.clone(), generics)
+                } else {
+                    Type::Path(f(p), generics)
+                }
+            }
+            &Type::Primitive(ref p) => {
+                Type::Primitive(p.clone())
+            }
+            &Type::Array(ref t, ref sz) => {
+                Type::Array(Box::new(t.map_paths_with_generics(generic_params, f)), sz.clone())
+            }
+            &Type::FuncPtr(ref ret, ref args, variadic, abi, nullable) => {
+                Type::FuncPtr(Box::new(ret.map_paths_with_generics(generic_params, f)),
+                              args.iter()
+                                  .map(|x| x.map_paths_with_generics(generic_params, f))
+                                  .collect(),
+                              variadic,
+                              abi,
+                              nullable)
             }
         }
     }
 
+    pub fn map_paths<F>(&self, f: &F) -> Type
+        where F: Fn(&PathRef) -> PathRef
+    {
+        self.map_paths_with_generics(&Vec::new(), f)
+    }
+
     fn can_cmp_order(&self) -> bool {
         match self {
             &Type::ConstPtr(..) => true,
@@ -281,42 +527,243 @@ impl Type {
         }
     }
 }
+/// The name of the item generated for a use of a generic item, like
+/// `Foo_i32` for `Foo<i32>`.
+pub fn mangle_path(path: &str, generics: &Vec<Type>, config: &Config) -> String {
+    if generics.is_empty() {
+        return String::from(path_name(path));
+    }
+
+    let args = generics.iter()
+                       .map(|x| x.mangle(config))
+                       .collect::<Vec<_>>()
+                       .join("_");
+
+    config.structure.generic_template.replace("{name}", path_name(path))
+                                     .replace("{args}", &args)
+}
+
+/// Writes the `template<..>` line of a generic item.
+fn write_template_params<F: Write>(generic_params: &Vec<PathRef>, out: &mut SourceWriter<F>) {
+    let params = generic_params.iter()
+                               .map(|x| format!("typename {}", x))
+                               .collect::<Vec<_>>();
+
+    out.write(&format!("template<{}>", params.join(", ")));
+    out.new_line();
+}
+
 impl Source for (String, Type) {
-    fn write<F: Write>(&self, _config: &Config, out: &mut SourceWriter<F>) {
-        cdecl::write_type(out, &self.1, &self.0);
+    fn write<F: Write>(&self, config: &Config, out: &mut SourceWriter<F>) {
+        cdecl::write_type(out, config, &self.1, &self.0);
+    }
+}
+
+/// A field of a struct or union, with its cfg and documentation.
+type Field = (String, Type, Option<Cfg>, Documentation);
+
+/// Renames the fields of a struct or union by its `field-names` or
+/// `rename-all` annotation, or else by `rename_fields` in the config.
+fn rename_fields(fields: &[Field],
+                 annotations: &AnnotationSet,
+                 config: &Config) -> Vec<Field> {
+    let rules = [annotations.parse_atom::<RenameRule>("rename-all"),
+                 config.structure.rename_fields];
+
+    if let Some(o) = annotations.list("field-names") {
+        let mut overriden_fields = Vec::new();
+
+        for (i, &(ref name, ref ty, ref cfg, ref documentation)) in fields.iter().enumerate() {
+            if i >= o.len() {
+                overriden_fields.push((name.clone(), ty.clone(), cfg.clone(), documentation.clone()));
+            } else {
+                overriden_fields.push((o[i].clone(), ty.clone(), cfg.clone(), documentation.clone()));
+            }
+        }
+
+        overriden_fields
+    } else if let Some(r) = find_first_some(&rules) {
+        fields.iter()
+              .map(|x| (r.apply_to_snake_case(&x.0,
+                                              IdentifierType::StructMember),
+                        x.1.clone(),
+                        x.2.clone(),
+                        x.3.clone()))
+              .collect()
+    } else {
+        fields.to_owned()
+    }
+}
+
+/// The fields of a struct or union with `generic_values` in place of its
+/// generic params.
+fn specialize_fields(fields: &[Field],
+                     generic_params: &[PathRef],
+                     generic_values: &[Type]) -> ConvertResult<Vec<Field>> {
+    if generic_values.len() != generic_params.len() {
+        return Err(format!("incomplete specialization"));
+    }
+
+    let mappings = generic_params.iter()
+                                 .zip(generic_values.iter())
+                                 .collect::<Vec<_>>();
+
+    Ok(fields.iter()
+             .map(|x| (x.0.clone(), x.1.specialize(&mappings), x.2.clone(), x.3.clone()))
+             .collect())
+}
+
+fn map_field_paths<F>(fields: &[Field],
+                      generic_params: &Vec<PathRef>,
+                      f: &F) -> Vec<Field>
+    where F: Fn(&PathRef) -> PathRef
+{
+    fields.iter()
+          .map(|x| (x.0.clone(), x.1.map_paths_with_generics(generic_params, f), x.2.clone(), x.3.clone()))
+          .collect()
+}
+
+/// Writes the fields of a struct or union, one per line and each in the
+/// `#if` of its cfg. The first field can be given an attribute.
+fn write_fields<F: Write>(fields: &Vec<Field>,
+                          first_attribute: Option<String>,
+                          config: &Config,
+                          out: &mut SourceWriter<F>) {
+    for (i, &(ref name, ref ty, ref cfg, ref documentation)) in fields.iter().enumerate() {
+        if i != 0 {
+            out.new_line();
+        }
+        cfg.write_before(config, out);
+        documentation.write(config, out);
+        if let (0, Some(ref attribute)) = (i, first_attribute.as_ref()) {
+            out.write(attribute);
+            out.write(" ");
+        }
+        cdecl::write_type(out, config, ty, name);
+        out.write(";");
+        cfg.write_after(config, out);
+    }
+}
+
+/// The calling convention of a function that can be called from C. The
+/// `-unwind` variants of an ABI use the same calling convention.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Abi {
+    C,
+    System,
+    Stdcall,
+    Fastcall,
+}
+
+impl Abi {
+    pub fn maybe(name: &str) -> Option<Abi> {
+        match name {
+            "C" | "C-unwind" => Some(Abi::C),
+            "system" | "system-unwind" => Some(Abi::System),
+            "stdcall" | "stdcall-unwind" => Some(Abi::Stdcall),
+            "fastcall" | "fastcall-unwind" => Some(Abi::Fastcall),
+            _ => None,
+        }
+    }
+
+    /// The name of the ABI in rust, which is used in the config.
+    pub fn name(&self) -> &'static str {
+        match self {
+            &Abi::C => "C",
+            &Abi::System => "system",
+            &Abi::Stdcall => "stdcall",
+            &Abi::Fastcall => "fastcall",
+        }
+    }
+}
+
+/// The annotations, documentation and attributes of a function, struct or
+/// enum that are carried over to its declaration.
+#[derive(Debug, Clone)]
+pub struct ItemAttributes {
+    pub annotations: AnnotationSet,
+    pub documentation: Documentation,
+    /// Whether a function is `#[must_use]`
+    pub must_use: bool,
+    /// Whether a function is `#[cold]`
+    pub cold: bool,
+    /// The note of a `#[deprecated]` item, which is empty if it doesn't
+    /// have one
+    pub deprecated: Option<String>,
+}
+
+impl ItemAttributes {
+    pub fn none() -> ItemAttributes {
+        ItemAttributes {
+            annotations: AnnotationSet::new(),
+            documentation: Documentation::none(),
+            must_use: false,
+            cold: false,
+            deprecated: None,
+        }
+    }
+
+    pub fn load<I: SynItemHelpers>(item: &I) -> ItemAttributes {
+        let annotations = match AnnotationSet::parse(item.get_doc_attr()) {
+            Ok(x) => x,
+            Err(msg) => {
+                warn!("{}", msg);
+                AnnotationSet::new()
+            }
+        };
+
+        ItemAttributes {
+            annotations: annotations,
+            documentation: item.get_documentation(),
+            must_use: item.is_must_use(),
+            cold: item.is_cold(),
+            deprecated: item.get_deprecated(),
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Function {
     pub name: String,
-    pub annotations: AnnotationSet,
+    pub cfg: Option<Cfg>,
+    pub attributes: ItemAttributes,
     pub ret: Type,
     pub args: Vec<(String, Type)>,
+    pub abi: Abi,
+    pub variadic: bool,
+    /// Whether the function returns `!`
+    pub no_return: bool,
     pub extern_decl: bool,
 }
 
 impl Function {
     pub fn convert(name: String,
-                   annotations: AnnotationSet,
+                   cfg: Option<Cfg>,
+                   attributes: ItemAttributes,
                    decl: &syn::FnDecl,
-                   extern_decl: bool) -> ConvertResult<Function>
+                   abi: Abi,
+                   extern_decl: bool,
+                   constants: &ConstantScope) -> ConvertResult<Function>
     {
         let args = try!(decl.inputs.iter()
-                                   .try_skip_map(|x| x.as_ident_and_type()));
-        let ret = try!(decl.output.as_type());
+                                   .try_skip_map(|x| x.as_ident_and_type(constants)));
+        let ret = try!(decl.output.as_type(constants));
 
         Ok(Function {
             name: name,
-            annotations: annotations,
+            cfg: cfg,
+            attributes: attributes,
             ret: ret,
             args: args,
+            abi: abi,
+            variadic: decl.variadic,
+            no_return: decl.output == syn::FunctionRetTy::Ty(syn::Ty::Never),
             extern_decl: extern_decl,
         })
     }
 
     pub fn apply_renaming(&mut self, config: &Config) {
-        let rules = [self.annotations.parse_atom::<RenameRule>("rename-all"),
+        let rules = [self.attributes.annotations.parse_atom::<RenameRule>("rename-all"),
                      config.function.rename_args];
 
         if let Some(r) = find_first_some(&rules) {
@@ -334,18 +781,59 @@ impl Function {
             ty.add_deps(library, out);
         }
     }
+
+    pub fn map_paths<F>(&self, f: &F) -> Function
+        where F: Fn(&PathRef) -> PathRef
+    {
+        Function {
+            ret: self.ret.map_paths(f),
+            args: self.args.iter()
+                           .map(|x| (x.0.clone(), x.1.map_paths(f)))
+                           .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// The configured attributes that apply to this function.
+    fn c_attributes(&self, config: &Config) -> Vec<String> {
+        let mut result = Vec::new();
+        if self.no_return {
+            result.extend(config.function.no_return.clone());
+        }
+        // Discarding nothing can't be warned about
+        let returns_void = match self.ret {
+            Type::Primitive(PrimitiveType::Void) => true,
+            _ => false,
+        };
+        if self.attributes.must_use && !returns_void {
+            result.extend(config.function.must_use.clone());
+        }
+        if self.attributes.cold {
+            result.extend(config.function.cold.clone());
+        }
+        if let Some(ref note) = self.attributes.deprecated {
+            result.extend(config.function.deprecated_attribute(note));
+        }
+        result
+    }
 }
 impl Source for Function {
     fn write<F: Write>(&self, config: &Config, out: &mut SourceWriter<F>) {
+        self.attributes.documentation.write(config, out);
+
         fn write_1<W: Write>(func: &Function, config: &Config, out: &mut SourceWriter<W>) {
-            let prefix = config.function.prefix(&func.annotations);
-            let postfix = config.function.postfix(&func.annotations);
+            let prefix = config.function.prefix(&func.attributes.annotations);
+            let postfix = config.function.postfix(&func.attributes.annotations);
 
+            for attribute in func.c_attributes(config) {
+                out.write(&attribute);
+                out.write(" ");
+            }
             if let Some(ref prefix) = prefix {
                 out.write(prefix);
                 out.write(" ");
             }
-            cdecl::write_func(out, &func);
+            cdecl::write_func(out, config, &func);
             if let Some(ref postfix) = postfix {
                 out.write(" ");
                 out.write(postfix);
@@ -353,14 +841,18 @@ impl Source for Function {
             out.write(";");
         }
         fn write_2<W: Write>(func: &Function, config: &Config, out: &mut SourceWriter<W>) {
-            let prefix = config.function.prefix(&func.annotations);
-            let postfix = config.function.postfix(&func.annotations);
+            let prefix = config.function.prefix(&func.attributes.annotations);
+            let postfix = config.function.postfix(&func.attributes.annotations);
 
+            for attribute in func.c_attributes(config) {
+                out.write(&attribute);
+                out.new_line();
+            }
             if let Some(ref prefix) = prefix {
                 out.write(prefix);
                 out.new_line();
             }
-            cdecl::write_func(out, &func);
+            cdecl::write_func(out, config, &func);
             if let Some(ref postfix) = postfix {
                 out.new_line();
                 out.write(postfix);
@@ -379,31 +871,95 @@ impl Source for Function {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct Static {
+    pub name: String,
+    pub cfg: Option<Cfg>,
+    pub documentation: Documentation,
+    pub ty: Type,
+    pub mutable: bool,
+}
+
+impl Static {
+    pub fn convert(name: String,
+                   cfg: Option<Cfg>,
+                   documentation: Documentation,
+                   ty: &syn::Ty,
+                   mutable: syn::Mutability,
+                   constants: &ConstantScope) -> ConvertResult<Static>
+    {
+        let ty = match Type::convert(ty, constants)? {
+            Some(ty) => ty,
+            None => return Err(format!("cannot have a static of a zero sized type")),
+        };
+
+        Ok(Static {
+            name: name,
+            cfg: cfg,
+            documentation: documentation,
+            ty: ty,
+            mutable: mutable == syn::Mutability::Mutable,
+        })
+    }
+
+    pub fn add_deps(&self, library: &Library, out: &mut DependencyGraph) {
+        self.ty.add_deps(library, out);
+    }
+
+    pub fn map_paths<F>(&self, f: &F) -> Static
+        where F: Fn(&PathRef) -> PathRef
+    {
+        Static {
+            ty: self.ty.map_paths(f),
+            ..self.clone()
+        }
+    }
+}
+impl Source for Static {
+    fn write<F: Write>(&self, config: &Config, out: &mut SourceWriter<F>) {
+        self.documentation.write(config, out);
+
+        out.write("extern ");
+        cdecl::write_static(out, config, &self.ty, &self.name, !self.mutable);
+        out.write(";");
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Struct {
     pub name: String,
-    pub annotations: AnnotationSet,
-    pub fields: Vec<(String, Type)>,
+    pub cfg: Option<Cfg>,
+    pub attributes: ItemAttributes,
+    pub alignment: Option<Alignment>,
+    pub fields: Vec<(String, Type, Option<Cfg>, Documentation)>,
     pub generic_params: Vec<PathRef>,
 }
 
 impl Struct {
     pub fn convert(name: String,
-                   annotations: AnnotationSet,
+                   cfg: Option<Cfg>,
+                   attributes: ItemAttributes,
+                   alignment: Option<Alignment>,
                    decl: &syn::VariantData,
-                   generics: &syn::Generics) -> ConvertResult<Struct>
+                   generics: &syn::Generics,
+                   constants: &ConstantScope) -> ConvertResult<Struct>
     {
         let fields = match decl {
             &syn::VariantData::Struct(ref fields) => {
                 try!(fields.iter()
-                           .try_skip_map(|x| x.as_ident_and_type()))
+                           .try_skip_map(|field| {
+                               field.as_ident_and_type(constants)
+                                    .map(|x| x.map(|(name, ty)| (name, ty, Cfg::load(&field.attrs), Documentation::load(&field.attrs))))
+                           }))
             }
             &syn::VariantData::Tuple(ref fields) => {
                 let mut out = Vec::new();
                 let mut current = 0;
                 for field in fields {
-                    if let Some(x) = try!(Type::convert(&field.ty)) {
-                        out.push((format!("{}", current), x));
+                    if let Some(x) = try!(Type::convert(&field.ty, constants)) {
+                        // Tuple fields are named by their index, which isn't a
+                        // valid identifier in C
+                        out.push((format!("_{}", current), x, Cfg::load(&field.attrs), Documentation::load(&field.attrs)));
                         current += 1;
                     }
                 }
@@ -420,55 +976,107 @@ impl Struct {
 
         Ok(Struct {
             name: name,
-            annotations: annotations,
+            cfg: cfg,
+            attributes: attributes,
+            alignment: alignment,
             fields: fields,
             generic_params: generic_params,
         })
     }
 
     pub fn apply_renaming(&mut self, config: &Config) {
-        let rules = [self.annotations.parse_atom::<RenameRule>("rename-all"),
-                     config.structure.rename_fields];
-
-        if let Some(o) = self.annotations.list("field-names") {
-            let mut overriden_fields = Vec::new();
-
-            for (i, &(ref name, ref ty)) in self.fields.iter().enumerate() {
-                if i >= o.len() {
-                    overriden_fields.push((name.clone(), ty.clone()));
-                } else {
-                    overriden_fields.push((o[i].clone(), ty.clone()));
-                }
-            }
-
-            self.fields = overriden_fields;
-        } else if let Some(r) = find_first_some(&rules) {
-            self.fields = self.fields.iter()
-                                     .map(|x| (r.apply_to_snake_case(&x.0,
-                                                                     IdentifierType::StructMember),
-                                               x.1.clone()))
-                                     .collect();
-        }
+        self.fields = rename_fields(&self.fields, &self.attributes.annotations, config);
     }
 
     pub fn add_deps(&self, library: &Library, out: &mut DependencyGraph) {
-        for &(_, ref ty) in &self.fields {
+        for &(_, ref ty, _, _) in &self.fields {
             ty.add_deps_with_generics(&self.generic_params, library, out);
         }
     }
+
+    /// A copy of this struct named `name`, with `generic_values` in place
+    /// of its generic params.
+    pub fn specialize(&self, name: &str, generic_values: &Vec<Type>) -> ConvertResult<Struct> {
+        Ok(Struct {
+            name: String::from(name),
+            fields: specialize_fields(&self.fields, &self.generic_params, generic_values)?,
+            generic_params: vec![],
+            ..self.clone()
+        })
+    }
+
+    pub fn map_paths<F>(&self, f: &F) -> Struct
+        where F: Fn(&PathRef) -> PathRef
+    {
+        Struct {
+            fields: map_field_paths(&self.fields, &self.generic_params, f),
+            ..self.clone()
+        }
+    }
 }
 impl Source for Struct {
     fn write<F: Write>(&self, config: &Config, out: &mut SourceWriter<F>) {
-        assert!(self.generic_params.is_empty());
+        assert!(self.generic_params.is_empty() || config.generics_as_templates());
+
+        self.attributes.documentation.write(config, out);
+
+        // The attributes go after the `struct` keyword, except for alignment
+        // in C, where only the fields of a struct can be aligned, and for
+        // deprecation in C, which goes on the typedef name that is used
+        let mut attributes = Vec::new();
+        let deprecated = self.attributes.deprecated.as_ref()
+                                                   .and_then(|note| config.structure.deprecated_attribute(note));
+        let typedef_attribute = if config.language == Language::C {
+            deprecated
+        } else {
+            attributes.extend(deprecated);
+            None
+        };
+        let field_attribute = match self.alignment {
+            Some(Alignment::Packed) => {
+                attributes.push(config.structure.packed_attribute());
+                None
+            }
+            Some(Alignment::Align(n)) if config.language == Language::C => {
+                // Aligning a later field would move it, so the first field
+                // has to be there whatever the cfg
+                match self.fields.first() {
+                    None => {
+                        warn!("can't align {} because it has no fields", self.name);
+                        None
+                    }
+                    Some(&(_, _, Some(_), _)) => {
+                        warn!("can't align {} because its first field has a cfg", self.name);
+                        None
+                    }
+                    Some(_) => Some(config.structure.align_attribute(&config.language, n)),
+                }
+            }
+            Some(Alignment::Align(n)) => {
+                attributes.push(config.structure.align_attribute(&config.language, n));
+                None
+            }
+            None => None,
+        };
+
+        if !self.generic_params.is_empty() {
+            write_template_params(&self.generic_params, out);
+        }
 
         if config.language == Language::C {
             out.write("typedef struct");
         } else {
-            out.write(&format!("struct {}", self.name));
+            out.write("struct");
+        }
+        for attribute in &attributes {
+            out.write(&format!(" {}", attribute));
+        }
+        if config.language == Language::Cxx {
+            out.write(&format!(" {}", self.name));
         }
         out.open_brace();
 
-        out.write_vertical_source_list(&self.fields, ListType::Cap(";"));
+        write_fields(&self.fields, field_attribute, config, out);
 
         if config.language == Language::Cxx {
             let mut wrote_start_newline = false;
@@ -498,27 +1106,30 @@ impl Source for Struct {
                 out.close_brace(false);
             };
 
-            if config.structure.derive_eq(&self.annotations) &&
+            // The operators can't refer to fields that may be compiled out
+            let has_cfg_fields = self.fields.iter().any(|x| x.2.is_some());
+
+            if config.structure.derive_eq(&self.attributes.annotations) && !has_cfg_fields &&
                !self.fields.is_empty() && self.fields.iter().all(|x| x.1.can_cmp_eq()) {
                 emit_op("==", "&&");
             }
-            if config.structure.derive_neq(&self.annotations) &&
+            if config.structure.derive_neq(&self.attributes.annotations) && !has_cfg_fields &&
                !self.fields.is_empty() && self.fields.iter().all(|x| x.1.can_cmp_eq()) {
                 emit_op("!=", "||");
             }
-            if config.structure.derive_lt(&self.annotations) &&
+            if config.structure.derive_lt(&self.attributes.annotations) && !has_cfg_fields &&
                self.fields.len() == 1 && self.fields[0].1.can_cmp_order() {
                 emit_op("<", "&&");
             }
-            if config.structure.derive_lte(&self.annotations) &&
+            if config.structure.derive_lte(&self.attributes.annotations) && !has_cfg_fields &&
                self.fields.len() == 1 && self.fields[0].1.can_cmp_order() {
                 emit_op("<=", "&&");
             }
-            if config.structure.derive_gt(&self.annotations) &&
+            if config.structure.derive_gt(&self.attributes.annotations) && !has_cfg_fields &&
                self.fields.len() == 1 && self.fields[0].1.can_cmp_order() {
                 emit_op(">", "&&");
             }
-            if config.structure.derive_gte(&self.annotations) &&
+            if config.structure.derive_gte(&self.attributes.annotations) && !has_cfg_fields &&
                self.fields.len() == 1 && self.fields[0].1.can_cmp_order() {
                 emit_op(">=", "&&");
             }
@@ -526,7 +1137,11 @@ impl Source for Struct {
 
         if config.language == Language::C {
             out.close_brace(false);
-            out.write(&format!(" {};", self.name));
+            out.write(&format!(" {}", self.name));
+            if let Some(ref attribute) = typedef_attribute {
+                out.write(&format!(" {}", attribute));
+            }
+            out.write(";");
         } else {
             out.close_brace(true);
         }
@@ -534,131 +1149,382 @@ impl Source for Struct {
 }
 
 #[derive(Debug, Clone)]
-pub struct OpaqueStruct {
-    pub name: PathRef,
+pub struct Union {
+    pub name: String,
+    pub cfg: Option<Cfg>,
     pub annotations: AnnotationSet,
+    pub documentation: Documentation,
+    pub fields: Vec<(String, Type, Option<Cfg>, Documentation)>,
+    pub generic_params: Vec<PathRef>,
 }
 
-impl OpaqueStruct {
-    pub fn new(name: String, annotations: AnnotationSet) -> OpaqueStruct
+impl Union {
+    pub fn convert(name: String,
+                   cfg: Option<Cfg>,
+                   annotations: AnnotationSet,
+                   documentation: Documentation,
+                   decl: &syn::VariantData,
+                   generics: &syn::Generics,
+                   constants: &ConstantScope) -> ConvertResult<Union>
     {
-        OpaqueStruct {
+        let fields = match decl {
+            &syn::VariantData::Struct(ref fields) => {
+                fields.iter()
+                      .try_skip_map(|field| {
+                          field.as_ident_and_type(constants)
+                               .map(|x| x.map(|(name, ty)| (name, ty, Cfg::load(&field.attrs), Documentation::load(&field.attrs))))
+                      })?
+            }
+            _ => {
+                return Err(format!("unions must have named fields"));
+            }
+        };
+
+        let generic_params = generics.ty_params.iter()
+                                               .map(|x| x.ident.to_string())
+                                               .collect::<Vec<_>>();
+
+        Ok(Union {
             name: name,
+            cfg: cfg,
             annotations: annotations,
+            documentation: documentation,
+            fields: fields,
+            generic_params: generic_params,
+        })
+    }
+
+    pub fn apply_renaming(&mut self, config: &Config) {
+        self.fields = rename_fields(&self.fields, &self.annotations, config);
+    }
+
+    pub fn add_deps(&self, library: &Library, out: &mut DependencyGraph) {
+        for &(_, ref ty, _, _) in &self.fields {
+            ty.add_deps_with_generics(&self.generic_params, library, out);
         }
     }
-}
-impl Source for OpaqueStruct {
-    fn write<F: Write>(&self, config: &Config, out: &mut SourceWriter<F>) {
-        if config.language == Language::C {
-            out.write(&format!("struct {};", self.name));
-            out.new_line();
-            out.write(&format!("typedef struct {} {};", self.name, self.name));
-        } else {
-            out.write(&format!("struct {};", self.name));
+
+    /// A copy of this union named `name`, with `generic_values` in place
+    /// of its generic params.
+    pub fn specialize(&self, name: &str, generic_values: &Vec<Type>) -> ConvertResult<Union> {
+        Ok(Union {
+            name: String::from(name),
+            fields: specialize_fields(&self.fields, &self.generic_params, generic_values)?,
+            generic_params: vec![],
+            ..self.clone()
+        })
+    }
+
+    pub fn map_paths<F>(&self, f: &F) -> Union
+        where F: Fn(&PathRef) -> PathRef
+    {
+        Union {
+            fields: map_field_paths(&self.fields, &self.generic_params, f),
+            ..self.clone()
+        }
+    }
+}
+impl Source for Union {
+    fn write<F: Write>(&self, config: &Config, out: &mut SourceWriter<F>) {
+        assert!(self.generic_params.is_empty() || config.generics_as_templates());
+
+        self.documentation.write(config, out);
+
+        if !self.generic_params.is_empty() {
+            write_template_params(&self.generic_params, out);
+        }
+
+        if config.language == Language::C {
+            out.write("typedef union");
+        } else {
+            out.write(&format!("union {}", self.name));
+        }
+        out.open_brace();
+
+        write_fields(&self.fields, None, config, out);
+
+        if config.language == Language::C {
+            out.close_brace(false);
+            out.write(&format!(" {};", self.name));
+        } else {
+            out.close_brace(true);
         }
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct OpaqueStruct {
+    pub name: PathRef,
+    pub cfg: Option<Cfg>,
+    pub annotations: AnnotationSet,
+    pub documentation: Documentation,
+    pub generic_params: Vec<PathRef>,
+}
+
+impl OpaqueStruct {
+    pub fn new(name: String,
+               generics: &syn::Generics,
+               cfg: Option<Cfg>,
+               annotations: AnnotationSet,
+               documentation: Documentation) -> OpaqueStruct
+    {
+        let generic_params = generics.ty_params.iter()
+                                               .map(|x| x.ident.to_string())
+                                               .collect::<Vec<_>>();
+
+        OpaqueStruct {
+            name: name,
+            cfg: cfg,
+            annotations: annotations,
+            documentation: documentation,
+            generic_params: generic_params,
+        }
+    }
+}
+impl Source for OpaqueStruct {
+    fn write<F: Write>(&self, config: &Config, out: &mut SourceWriter<F>) {
+        assert!(self.generic_params.is_empty() || config.generics_as_templates());
+
+        self.documentation.write(config, out);
+
+        if !self.generic_params.is_empty() {
+            write_template_params(&self.generic_params, out);
+        }
+
+        if config.language == Language::C {
+            out.write(&format!("struct {};", self.name));
+            out.new_line();
+            out.write(&format!("typedef struct {} {};", self.name, self.name));
+        } else {
+            out.write(&format!("struct {};", self.name));
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EnumVariant {
+    pub name: String,
+    pub cfg: Option<Cfg>,
+    pub documentation: Documentation,
+    /// The value of the variant, unless it has no discriminant and comes
+    /// after a variant with a cfg. Its value then depends on which of
+    /// those variants are compiled, so it is left for C to count on from
+    /// the variant before it
+    pub discriminant: Option<i128>,
+    /// The name of the union member and the struct of the fields, for
+    /// variants that carry data
+    pub body: Option<(String, Struct)>,
+}
+
+impl EnumVariant {
+    fn convert(enum_name: &str,
+               variant: &syn::Variant,
+               discriminant: Option<i128>,
+               constants: &ConstantScope) -> ConvertResult<EnumVariant>
+    {
+        let body = match variant.data {
+            syn::VariantData::Unit => None,
+            syn::VariantData::Struct(_) |
+            syn::VariantData::Tuple(_) => {
+                let body = Struct::convert(format!("{}_{}_Body", enum_name, variant.ident),
+                                           None,
+                                           ItemAttributes::none(),
+                                           None,
+                                           &variant.data,
+                                           &syn::Generics::default(),
+                                           constants)?;
+
+                let member_name = RenameRule::SnakeCase.apply_to_pascal_case(&variant.ident.to_string(),
+                                                                             IdentifierType::StructMember);
+                Some((member_name, body))
+            }
+        };
+
+        Ok(EnumVariant {
+            name: variant.ident.to_string(),
+            cfg: Cfg::load(&variant.attrs),
+            documentation: Documentation::load(&variant.attrs),
+            discriminant: discriminant,
+            body: body,
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Enum {
     pub name: String,
+    pub cfg: Option<Cfg>,
     pub repr: Repr,
-    pub annotations: AnnotationSet,
-    pub values: Vec<(String, u64)>,
+    /// Whether `repr(C)` was given for an enum with fields, which selects
+    /// between the two layouts specified by RFC 2195
+    pub repr_c: bool,
+    pub attributes: ItemAttributes,
+    pub variants: Vec<EnumVariant>,
 }
 
 impl Enum {
     pub fn convert(name: String,
+                   cfg: Option<Cfg>,
                    repr: Repr,
-                   annotations: AnnotationSet,
-                   variants: &Vec<syn::Variant>) -> ConvertResult<Enum>
+                   repr_c: bool,
+                   attributes: ItemAttributes,
+                   variants: &Vec<syn::Variant>,
+                   constants: &ConstantScope) -> ConvertResult<Enum>
     {
-        if repr != Repr::U32 &&
-           repr != Repr::U16 &&
-           repr != Repr::U8 {
-            return if repr == Repr::C {
-                Err(format!("repr(C) is not FFI safe for enums"))
-            } else {
-                Err(format!("enum not marked with a repr(u32) or repr(u16) or repr(u8)"))
-            };
+        if repr == Repr::None {
+            return Err(format!("enum not marked with a repr(C) or integer repr"));
         }
 
-        let mut values = Vec::new();
-        let mut current = 0;
+        let mut converted = Vec::new();
+        let mut next = Some(0);
 
         for variant in variants {
-            match variant.data {
-                syn::VariantData::Unit => {
-                    match variant.discriminant {
-                        Some(syn::ConstExpr::Lit(syn::Lit::Int(i, _))) => {
-                            current = i;
-                        }
-                        Some(_) => {
-                            return Err(format!("unsupported discriminant"));
-                        }
-                        None => { /* okay, we just use current */ }
-                    }
-
-                    values.push((variant.ident.to_string(), current));
-                    current = current + 1;
-                }
-                _ => {
-                    return Err(format!("unsupported variant"));
+            let discriminant = match variant.discriminant {
+                Some(ref discriminant) => {
+                    let value = const_eval::evaluate(discriminant, repr.type_name(), constants)
+                                           .map_err(|msg| format!("unsupported discriminant for {} - ({})",
+                                                                  variant.ident,
+                                                                  msg))?;
+                    Some(value)
                 }
-            }
+                None => next,
+            };
+
+            let variant = EnumVariant::convert(&name, variant, discriminant, constants)?;
+            next = match variant.cfg {
+                Some(_) => None,
+                None => discriminant.map(|x| x + 1),
+            };
+            converted.push(variant);
         }
 
-        if let Some(variants) = annotations.list("enum-trailing-values") {
+        if let Some(variants) = attributes.annotations.list("enum-trailing-values") {
             for variant in variants {
-                values.push((variant, current));
-                current = current + 1;
+                converted.push(EnumVariant {
+                    name: variant,
+                    cfg: None,
+                    documentation: Documentation::none(),
+                    discriminant: next,
+                    body: None,
+                });
+                next = next.map(|x| x + 1);
             }
         }
 
         Ok(Enum {
             name: name,
+            cfg: cfg,
             repr: repr,
-            annotations: annotations,
-            values: values,
+            repr_c: repr_c,
+            attributes: attributes,
+            variants: converted,
         })
     }
 
+    /// Whether any variant carries data, requiring a tagged union.
+    pub fn has_fields(&self) -> bool {
+        self.variants.iter().any(|x| x.body.is_some())
+    }
+
+    fn tag_name(&self) -> String {
+        if self.has_fields() {
+            format!("{}_Tag", self.name)
+        } else {
+            self.name.clone()
+        }
+    }
+
     pub fn apply_renaming(&mut self, config: &Config) {
-        let rules = [self.annotations.parse_atom::<RenameRule>("rename-all"),
+        let rules = [self.attributes.annotations.parse_atom::<RenameRule>("rename-all"),
                      config.enumeration.rename_variants];
 
         if let Some(r) = find_first_some(&rules) {
-            self.values = self.values.iter()
-                                     .map(|x| (r.apply_to_pascal_case(&x.0,
-                                                                      IdentifierType::EnumVariant),
-                                               x.1.clone()))
-                                     .collect();
+            for variant in &mut self.variants {
+                variant.name = r.apply_to_pascal_case(&variant.name,
+                                                      IdentifierType::EnumVariant);
+            }
+        }
+
+        for variant in &mut self.variants {
+            if let Some((_, ref mut body)) = variant.body {
+                body.apply_renaming(config);
+            }
         }
     }
-}
-impl Source for Enum {
-    fn write<F: Write>(&self, config: &Config, out: &mut SourceWriter<F>) {
-        let size = match self.repr {
-            Repr::U32 => "uint32_t",
-            Repr::U16 => "uint16_t",
-            Repr::U8 => "uint8_t",
-            _ => unreachable!(),
+
+    pub fn add_deps(&self, library: &Library, out: &mut DependencyGraph) {
+        for variant in &self.variants {
+            if let Some((_, ref body)) = variant.body {
+                body.add_deps(library, out);
+            }
+        }
+    }
+
+    pub fn map_paths<F>(&self, f: &F) -> Enum
+        where F: Fn(&PathRef) -> PathRef
+    {
+        Enum {
+            variants: self.variants.iter()
+                                   .map(|x| EnumVariant {
+                                       body: x.body.as_ref().map(|&(ref member, ref body)| {
+                                           (member.clone(), body.map_paths(f))
+                                       }),
+                                       ..x.clone()
+                                   })
+                                   .collect(),
+            ..self.clone()
+        }
+    }
+
+    fn deprecated_attribute(&self, config: &Config) -> Option<String> {
+        self.attributes.deprecated.as_ref()
+                                  .and_then(|note| config.enumeration.deprecated_attribute(note))
+    }
+
+    fn write_tag<F: Write>(&self, config: &Config, out: &mut SourceWriter<F>) {
+        // A `repr(C)` enum has the same representation as a C enum, so
+        // the underlying type is left up to the C compiler
+        let size = self.repr.type_name()
+                            .and_then(PrimitiveType::maybe)
+                            .map(|x| x.c_name(config));
+        let tag_name = self.tag_name();
+
+        // The tag is only documented and deprecated when it is the whole
+        // enum. In C the attribute goes on the typedef, which is the name
+        // that is used
+        let attribute = if self.has_fields() {
+            None
+        } else {
+            self.attributes.documentation.write(config, out);
+            self.deprecated_attribute(config)
         };
 
         if config.language == Language::C {
-            out.write(&format!("enum {}", self.name));
+            out.write(&format!("enum {}", tag_name));
         } else {
-            out.write(&format!("enum class {} : {}", self.name, size));
+            out.write("enum class");
+            if let Some(ref attribute) = attribute {
+                out.write(&format!(" {}", attribute));
+            }
+            out.write(&format!(" {}", tag_name));
+            if let Some(ref size) = size {
+                out.write(&format!(" : {}", size));
+            }
         }
         out.open_brace();
-        for (i, value) in self.values.iter().enumerate() {
+        for (i, variant) in self.variants.iter().enumerate() {
             if i != 0 {
                 out.new_line()
             }
-            out.write(&format!("{} = {},", value.0, value.1));
+            variant.cfg.write_before(config, out);
+            variant.documentation.write(config, out);
+            match variant.discriminant {
+                Some(value) => out.write(&format!("{} = {},", variant.name, value)),
+                None => out.write(&format!("{},", variant.name)),
+            }
+            variant.cfg.write_after(config, out);
         }
-        if config.enumeration.add_sentinel(&self.annotations) {
+        if config.enumeration.add_sentinel(&self.attributes.annotations) {
             out.new_line();
             out.new_line();
             out.write("Sentinel /* this must be last for serialization purposes. */");
@@ -667,7 +1533,93 @@ impl Source for Enum {
 
         if config.language == Language::C {
             out.new_line();
-            out.write(&format!("typedef {} {};", size, self.name));
+            if let Some(size) = size {
+                out.write(&format!("typedef {} {}", size, tag_name));
+            } else {
+                out.write(&format!("typedef enum {} {}", tag_name, tag_name));
+            }
+            if let Some(ref attribute) = attribute {
+                out.write(&format!(" {}", attribute));
+            }
+            out.write(";");
+        }
+    }
+}
+impl Source for Enum {
+    fn write<F: Write>(&self, config: &Config, out: &mut SourceWriter<F>) {
+        self.write_tag(config, out);
+
+        if !self.has_fields() {
+            return;
+        }
+
+        let tag_name = self.tag_name();
+
+        // Without `repr(C)`, every body starts with its own copy of the tag
+        // and the enum is a union of the bodies
+        for variant in &self.variants {
+            if let Some((_, ref body)) = variant.body {
+                let mut body = body.clone();
+                if !self.repr_c {
+                    body.fields.insert(0, (String::from("tag"), Type::Path(tag_name.clone(), Vec::new()), None, Documentation::none()));
+                }
+
+                out.new_line();
+                out.new_line();
+                variant.cfg.write_before(config, out);
+                body.write(config, out);
+                variant.cfg.write_after(config, out);
+            }
+        }
+
+        out.new_line();
+        out.new_line();
+
+        self.attributes.documentation.write(config, out);
+        let keyword = if self.repr_c { "struct" } else { "union" };
+        let attribute = self.deprecated_attribute(config);
+        if config.language == Language::C {
+            out.write(&format!("typedef {}", keyword));
+        } else {
+            out.write(keyword);
+            if let Some(ref attribute) = attribute {
+                out.write(&format!(" {}", attribute));
+            }
+            out.write(&format!(" {}", self.name));
+        }
+        out.open_brace();
+
+        out.write(&format!("{} tag;", tag_name));
+        if self.repr_c {
+            out.new_line();
+            out.write("union");
+            out.open_brace();
+        }
+        let mut needs_new_line = !self.repr_c;
+        for variant in &self.variants {
+            if let Some((ref member_name, ref body)) = variant.body {
+                if needs_new_line {
+                    out.new_line();
+                }
+                variant.cfg.write_before(config, out);
+                out.write(&format!("{} {};", body.name, member_name));
+                variant.cfg.write_after(config, out);
+                needs_new_line = true;
+            }
+        }
+        if self.repr_c {
+            out.close_brace(true);
+        }
+
+        if config.language == Language::C {
+            out.close_brace(false);
+            out.write(&format!(" {}", self.name));
+            if let Some(ref attribute) = attribute {
+                out.write(&format!(" {}", attribute));
+            }
+            out.write(";");
+        } else {
+            out.close_brace(true);
         }
     }
 }
@@ -675,7 +1627,9 @@ impl Source for Enum {
 #[derive(Debug, Clone)]
 pub struct Specialization {
     pub name: String,
+    pub cfg: Option<Cfg>,
     pub annotations: AnnotationSet,
+    pub documentation: Documentation,
     pub aliased: PathRef,
     pub generic_params: Vec<PathRef>,
     pub generic_values: Vec<Type>,
@@ -683,9 +1637,12 @@ pub struct Specialization {
 
 impl Specialization {
     pub fn convert(name: String,
+                   cfg: Option<Cfg>,
                    annotations: AnnotationSet,
+                   documentation: Documentation,
                    generics: &syn::Generics,
-                   ty: &syn::Ty) -> ConvertResult<Specialization>
+                   ty: &syn::Ty,
+                   constants: &ConstantScope) -> ConvertResult<Specialization>
     {
         match ty {
             &syn::Ty::Path(ref _q, ref p) => {
@@ -693,15 +1650,17 @@ impl Specialization {
                                                        .map(|x| x.ident.to_string())
                                                        .collect::<Vec<_>>();
 
-                let (path, generic_values) = try!(p.convert_to_generic_single_segment());
+                let (path, generic_values) = try!(p.convert_to_generic_path(constants));
 
-                if PrimitiveType::maybe(&path).is_some() {
+                if p.is_c_type_path() && PrimitiveType::maybe(path_name(&path)).is_some() {
                     return Err(format!("can't specialize a primitive"));
                 }
 
                 Ok(Specialization {
                     name: name,
+                    cfg: cfg,
                     annotations: annotations,
+                    documentation: documentation,
                     aliased: path,
                     generic_params: generic_params,
                     generic_values: generic_values,
@@ -714,106 +1673,203 @@ impl Specialization {
     }
 
     pub fn add_deps(&self, library: &Library, out: &mut DependencyGraph) {
-        library.add_deps_for_path_deps(&self.aliased, out);
-        for value in &self.generic_values {
-            value.add_deps_with_generics(&self.generic_params, &library, out);
+        if library.config().generics_as_templates() {
+            library.add_deps_for_path(&self.aliased, out);
+            for value in &self.generic_values {
+                value.add_deps_with_generics(&self.generic_params, library, out);
+            }
+            return;
+        }
+
+        // The specialized items can depend on more than the aliased item,
+        // such as on `Foo<i32>` for a field of type `Foo<T>`. Errors are
+        // reported when the specialization is generated.
+        if let Ok(values) = self.specialize(library) {
+            for value in values {
+                value.add_deps(library, out);
+            }
         }
     }
 
-    pub fn specialize(&self, config: &Config, library: &Library) -> ConvertResult<Option<PathValue>> {
+    pub fn map_paths<F>(&self, f: &F) -> Specialization
+        where F: Fn(&PathRef) -> PathRef
+    {
+        Specialization {
+            aliased: f(&self.aliased),
+            generic_values: self.generic_values.iter()
+                                               .map(|x| x.map_paths_with_generics(&self.generic_params, f))
+                                               .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// The attributes of a specialization of an item with `aliased`, which
+    /// is documented as the specialization but keeps the other attributes.
+    fn attributes_of(&self, aliased: &ItemAttributes) -> ItemAttributes {
+        ItemAttributes {
+            annotations: self.annotations.clone(),
+            documentation: self.documentation.clone(),
+            ..aliased.clone()
+        }
+    }
+
+    /// Specializes the aliased item, once for each of its definitions.
+    pub fn specialize(&self, library: &Library) -> ConvertResult<Vec<PathValue>> {
+        self.specialize_with_aliases(library, &mut Vec::new())
+    }
+
+    /// Specializes the aliased item, where `aliases` are the paths of the
+    /// aliases that have been followed to get here, to stop at cycles.
+    fn specialize_with_aliases(&self, library: &Library, aliases: &mut Vec<PathRef>) -> ConvertResult<Vec<PathValue>> {
         if self.generic_params.len() > 0 {
-            return Ok(None);
+            return Ok(Vec::new());
         }
 
-        match library.resolve_path(&self.aliased) {
-            Some(aliased) => {
-                match aliased {
-                    PathValue::OpaqueStruct(_) => {
-                        Ok(Some(PathValue::OpaqueStruct(OpaqueStruct {
-                            name: self.name.clone(),
-                            annotations: self.annotations.clone(),
-                        })))
-                    }
-                    PathValue::Struct(aliased) => {
-                        if self.generic_values.len() !=
-                           aliased.generic_params.len() {
-                            return Err(format!("incomplete specialization"));
-                        }
+        let definitions = library.resolve_path(&self.aliased);
+        if definitions.is_empty() {
+            return Err(format!("couldn't find aliased type"));
+        }
 
-                        let mappings = aliased.generic_params.iter()
-                                                             .zip(self.generic_values.iter())
-                                                             .collect::<Vec<_>>();
-
-                        Ok(Some(PathValue::Struct(Struct {
-                            name: self.name.clone(),
-                            annotations: self.annotations.clone(),
-                            fields: aliased.fields.iter()
-                                                  .map(|x| (x.0.clone(), x.1.specialize(&mappings)))
-                                                  .collect(),
-                            generic_params: vec![],
-                        })))
-                    }
-                    PathValue::Enum(aliased) => {
-                        Ok(Some(PathValue::Enum(Enum {
-                            name: self.name.clone(),
-                            repr: aliased.repr.clone(),
-                            annotations: self.annotations.clone(),
-                            values: aliased.values.clone(),
-                        })))
-                    }
-                    PathValue::Typedef(aliased) => {
-                        Ok(Some(PathValue::Typedef(Typedef {
-                            name: self.name.clone(),
-                            annotations: self.annotations.clone(),
-                            aliased: aliased.aliased.clone(),
-                        })))
+        let mut result = Vec::new();
+        for aliased in definitions {
+            // A definition behind a cfg is only specialized under that cfg
+            let cfg = Cfg::join(&self.cfg, aliased.cfg().clone());
+
+            match aliased {
+                PathValue::OpaqueStruct(_) => {
+                    result.push(PathValue::OpaqueStruct(OpaqueStruct {
+                        name: self.name.clone(),
+                        cfg: cfg,
+                        annotations: self.annotations.clone(),
+                        documentation: self.documentation.clone(),
+                        generic_params: Vec::new(),
+                    }));
+                }
+                PathValue::Struct(aliased) => {
+                    result.push(PathValue::Struct(Struct {
+                        cfg: cfg,
+                        attributes: self.attributes_of(&aliased.attributes),
+                        ..aliased.specialize(&self.name, &self.generic_values)?
+                    }));
+                }
+                PathValue::Union(aliased) => {
+                    result.push(PathValue::Union(Union {
+                        cfg: cfg,
+                        annotations: self.annotations.clone(),
+                        documentation: self.documentation.clone(),
+                        ..aliased.specialize(&self.name, &self.generic_values)?
+                    }));
+                }
+                PathValue::Enum(aliased) => {
+                    result.push(PathValue::Enum(Enum {
+                        name: self.name.clone(),
+                        cfg: cfg,
+                        repr: aliased.repr.clone(),
+                        repr_c: aliased.repr_c,
+                        attributes: self.attributes_of(&aliased.attributes),
+                        variants: aliased.variants.clone(),
+                    }));
+                }
+                PathValue::Typedef(aliased) => {
+                    result.push(PathValue::Typedef(Typedef {
+                        name: self.name.clone(),
+                        cfg: cfg,
+                        annotations: self.annotations.clone(),
+                        documentation: self.documentation.clone(),
+                        aliased: aliased.aliased.clone(),
+                    }));
+                }
+                PathValue::Bitflags(aliased) => {
+                    result.push(PathValue::Bitflags(Bitflags {
+                        name: self.name.clone(),
+                        cfg: cfg,
+                        annotations: self.annotations.clone(),
+                        documentation: self.documentation.clone(),
+                        ..aliased
+                    }));
+                }
+                PathValue::Specialization(aliased) => {
+                    // Rust rejects cycles of aliases, unless each definition
+                    // along it is under a cfg that can't hold with the others
+                    if aliases.contains(&aliased.aliased) {
+                        info!("skip {} through {} - (type aliases form a cycle)", self.name, aliased.aliased);
+                        continue;
                     }
-                    PathValue::Specialization(aliased) => {
-                        if self.generic_values.len() !=
-                           aliased.generic_params.len() {
-                            return Err(format!("incomplete specialization"));
-                        }
 
-                        let mappings = aliased.generic_params.iter()
-                                                             .zip(self.generic_values.iter())
-                                                             .collect::<Vec<_>>();
-
-                        let generic_values = aliased.generic_values.iter()
-                                                                   .map(|x| x.specialize(&mappings))
-                                                                   .collect();
-
-                        Specialization {
-                            name: self.name.clone(),
-                            annotations: self.annotations.clone(),
-                            aliased: aliased.aliased.clone(),
-                            generic_params: Vec::new(),
-                            generic_values: generic_values,
-                        }.specialize(config, library)
+                    if self.generic_values.len() !=
+                       aliased.generic_params.len() {
+                        return Err(format!("incomplete specialization"));
                     }
+
+                    let mappings = aliased.generic_params.iter()
+                                                         .zip(self.generic_values.iter())
+                                                         .collect::<Vec<_>>();
+
+                    let generic_values = aliased.generic_values.iter()
+                                                               .map(|x| x.specialize(&mappings))
+                                                               .collect();
+
+                    let specialization = Specialization {
+                        name: self.name.clone(),
+                        cfg: cfg,
+                        annotations: self.annotations.clone(),
+                        documentation: self.documentation.clone(),
+                        aliased: aliased.aliased.clone(),
+                        generic_params: Vec::new(),
+                        generic_values: generic_values,
+                    };
+
+                    aliases.push(self.aliased.clone());
+                    let specialized = specialization.specialize_with_aliases(library, aliases);
+                    aliases.pop();
+
+                    result.extend(specialized?);
                 }
             }
-            None => {
-                Err(format!("couldn't find aliased type"))
-            }
         }
+
+        Ok(result)
+    }
+}
+
+impl Source for Specialization {
+    fn write<F: Write>(&self, config: &Config, out: &mut SourceWriter<F>) {
+        assert!(config.generics_as_templates());
+
+        self.documentation.write(config, out);
+
+        if !self.generic_params.is_empty() {
+            write_template_params(&self.generic_params, out);
+        }
+
+        out.write(&format!("using {} = ", self.name));
+        cdecl::write_abstract_type(out, config, &Type::Path(self.aliased.clone(), self.generic_values.clone()));
+        out.write(";");
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Typedef {
     pub name: String,
+    pub cfg: Option<Cfg>,
     pub annotations: AnnotationSet,
+    pub documentation: Documentation,
     pub aliased: Type,
 }
 
 impl Typedef {
     pub fn convert(name: String,
+                   cfg: Option<Cfg>,
                    annotations: AnnotationSet,
-                   ty: &syn::Ty) -> ConvertResult<Typedef> {
-        if let Some(x) = try!(Type::convert(ty)) {
+                   documentation: Documentation,
+                   ty: &syn::Ty,
+                   constants: &ConstantScope) -> ConvertResult<Typedef> {
+        if let Some(x) = try!(Type::convert(ty, constants)) {
             Ok(Typedef {
                 name: name,
+                cfg: cfg,
                 annotations: annotations,
+                documentation: documentation,
                 aliased: x,
             })
         } else {
@@ -824,11 +1880,350 @@ impl Typedef {
     pub fn add_deps(&self, library: &Library, out: &mut DependencyGraph) {
         self.aliased.add_deps(library, out);
     }
+
+    pub fn map_paths<F>(&self, f: &F) -> Typedef
+        where F: Fn(&PathRef) -> PathRef
+    {
+        Typedef {
+            aliased: self.aliased.map_paths(f),
+            ..self.clone()
+        }
+    }
 }
 impl Source for Typedef {
     fn write<F: Write>(&self, config: &Config, out: &mut SourceWriter<F>) {
+        self.documentation.write(config, out);
+
         out.write("typedef ");
         (self.name.clone(), self.aliased.clone()).write(config, out);
         out.write(";");
     }
 }
+
+#[derive(Debug, Clone)]
+pub struct Bitflags {
+    pub name: String,
+    pub cfg: Option<Cfg>,
+    pub annotations: AnnotationSet,
+    pub documentation: Documentation,
+    pub repr: PrimitiveType,
+    /// The flags, named by the flag alone until renaming
+    pub flags: Vec<Constant>,
+}
+
+impl Bitflags {
+    pub fn convert(name: String,
+                   cfg: Option<Cfg>,
+                   annotations: AnnotationSet,
+                   documentation: Documentation,
+                   ty: &syn::Ty,
+                   flags: &[syn::Item],
+                   constants: &ConstantScope) -> ConvertResult<Bitflags>
+    {
+        let repr = match Type::convert(ty, constants)? {
+            Some(Type::Primitive(prim)) => prim,
+            _ => return Err(format!("bitflags must have an integer type")),
+        };
+
+        // Flags can be defined in terms of the flags before them
+        let mut constants = constants.clone();
+        let mut converted = Vec::new();
+
+        for flag in flags {
+            let expr = match flag.node {
+                syn::ItemKind::Const(_, ref expr) => expr,
+                _ => continue,
+            };
+
+            let value = match const_eval::evaluate_expr(expr, Some(repr.rust_name()), &constants) {
+                Ok(value) => value,
+                Err(msg) => {
+                    warn!("skipping flag {}::{} - ({})", name, flag.ident, msg);
+                    continue;
+                }
+            };
+            constants.insert_local(flag.ident.to_string(), value);
+
+            converted.push(Constant {
+                name: flag.ident.to_string(),
+                cfg: Cfg::load(&flag.attrs),
+                annotations: AnnotationSet::parse(flag.get_doc_attr()).unwrap_or(AnnotationSet::new()),
+                documentation: Documentation::load(&flag.attrs),
+                ty: Type::Primitive(repr.clone()),
+                value: format!("{}", value),
+            });
+        }
+
+        Ok(Bitflags {
+            name: name,
+            cfg: cfg,
+            annotations: annotations,
+            documentation: documentation,
+            repr: repr,
+            flags: converted,
+        })
+    }
+
+    pub fn apply_renaming(&mut self, config: &Config) {
+        // Flags are prefixed with the type, as C has no associated constants
+        for flag in &mut self.flags {
+            flag.name = format!("{}_{}",
+                                self.name,
+                                Constant::renamed(&flag.name, &flag.annotations, config));
+        }
+    }
+
+    fn write_struct<F: Write>(&self, config: &Config, out: &mut SourceWriter<F>) {
+        let repr = self.repr.c_name(config);
+
+        let other = if let Some(r) = config.function.rename_args {
+            r.apply_to_snake_case("other", IdentifierType::FunctionArg)
+        } else {
+            String::from("other")
+        };
+
+        out.write(&format!("struct {}", self.name));
+        out.open_brace();
+        out.write(&format!("{} bits;", repr));
+        out.new_line();
+
+        out.new_line();
+        out.write("explicit operator bool() const");
+        out.open_brace();
+        out.write("return !!bits;");
+        out.close_brace(false);
+
+        out.new_line();
+        out.write(&format!("{} operator~() const", self.name));
+        out.open_brace();
+        out.write(&format!("return {{static_cast<{}>(~bits)}};", repr));
+        out.close_brace(false);
+
+        for op in &["|", "&", "^"] {
+            out.new_line();
+            out.write(&format!("{} operator{}(const {}& {}) const", self.name, op, self.name, other));
+            out.open_brace();
+            out.write(&format!("return {{static_cast<{}>(this->bits {} {}.bits)}};", repr, op, other));
+            out.close_brace(false);
+
+            out.new_line();
+            out.write(&format!("{}& operator{}=(const {}& {})", self.name, op, self.name, other));
+            out.open_brace();
+            out.write(&format!("*this = (*this {} {});", op, other));
+            out.new_line();
+            out.write("return *this;");
+            out.close_brace(false);
+        }
+
+        out.close_brace(true);
+    }
+}
+impl Source for Bitflags {
+    fn write<F: Write>(&self, config: &Config, out: &mut SourceWriter<F>) {
+        self.documentation.write(config, out);
+
+        let as_struct = config.language == Language::Cxx &&
+                        config.bitflags.cxx_struct(&self.annotations);
+
+        if as_struct {
+            self.write_struct(config, out);
+        } else {
+            out.write("typedef ");
+            (self.name.clone(), Type::Primitive(self.repr.clone())).write(config, out);
+            out.write(";");
+        }
+
+        for flag in &self.flags {
+            out.new_line();
+            flag.cfg.write_before(config, out);
+            flag.documentation.write(config, out);
+            if config.language == Language::C {
+                out.write(&format!("#define {} {}", flag.name, flag.value));
+            } else if as_struct {
+                out.write(&format!("static constexpr {} {} = {{ {} }};", self.name, flag.name, flag.value));
+            } else {
+                out.write(&format!("static constexpr {} {} = {};", self.name, flag.name, flag.value));
+            }
+            flag.cfg.write_after(config, out);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Constant {
+    pub name: String,
+    pub cfg: Option<Cfg>,
+    pub annotations: AnnotationSet,
+    pub documentation: Documentation,
+    pub ty: Type,
+    pub value: String,
+}
+
+impl Constant {
+    pub fn convert(name: String,
+                   cfg: Option<Cfg>,
+                   annotations: AnnotationSet,
+                   documentation: Documentation,
+                   ty: &syn::Ty,
+                   expr: &syn::Expr,
+                   constants: &ConstantScope) -> ConvertResult<Constant>
+    {
+        let literal = match &expr.node {
+            &syn::ExprKind::Lit(ref lit) => Some((lit, false)),
+            &syn::ExprKind::Unary(syn::UnOp::Neg, ref inner) => {
+                match &inner.node {
+                    &syn::ExprKind::Lit(ref lit) => Some((lit, true)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+
+        let (value, is_str) = match literal {
+            Some((lit, negate)) => {
+                (Constant::convert_lit(lit, negate)?, !negate && Constant::is_str_lit(lit))
+            }
+            None => {
+                // Integer constant expressions were evaluated when the module
+                // was collected
+                match constants.get(&name) {
+                    Some(value) => (format!("{}", value), false),
+                    None => {
+                        return Err(match const_eval::evaluate_expr(expr, None, constants) {
+                            Err(msg) => msg,
+                            Ok(_) => format!("unsupported constant expression"),
+                        });
+                    }
+                }
+            }
+        };
+
+        // String literals are `&'static str` in rust, and are exposed as
+        // a pointer to a null terminated C string
+        let ty = if is_str {
+            Type::ConstPtr(Box::new(Type::Primitive(PrimitiveType::Char)), false)
+        } else {
+            match Type::convert(ty, constants)? {
+                Some(Type::Primitive(prim)) => Type::Primitive(prim),
+                Some(_) => return Err(format!("constants must have a primitive type")),
+                None => return Err(format!("cannot have a constant of a zero sized type")),
+            }
+        };
+
+        Ok(Constant {
+            name: name,
+            cfg: cfg,
+            annotations: annotations,
+            documentation: documentation,
+            ty: ty,
+            value: value,
+        })
+    }
+
+    fn is_str_lit(lit: &syn::Lit) -> bool {
+        match lit {
+            &syn::Lit::Str(..) => true,
+            _ => false,
+        }
+    }
+
+    fn convert_lit(lit: &syn::Lit, negate: bool) -> ConvertResult<String> {
+        let sign = if negate { "-" } else { "" };
+
+        match lit {
+            // Decimal literals above `INT64_MAX` would be taken as signed
+            // and then warned about as too large for it
+            &syn::Lit::Int(x, _) if x > i64::MAX as u64 => {
+                if !negate {
+                    Ok(format!("{}ULL", x))
+                } else if x == 1 << 63 {
+                    Ok(format!("(-{}LL - 1)", x - 1))
+                } else {
+                    Err(format!("integer literal is out of range"))
+                }
+            }
+            &syn::Lit::Int(x, _) => Ok(format!("{}{}", sign, x)),
+            &syn::Lit::Float(ref x, _) => Ok(format!("{}{}", sign, x.replace("_", ""))),
+            &syn::Lit::Byte(x) if !negate => Ok(format!("{}", x)),
+            &syn::Lit::Char(x) if !negate => Ok(format!("{}", x as u32)),
+            &syn::Lit::Bool(x) if !negate => Ok(format!("{}", x)),
+            &syn::Lit::Str(ref x, _) if !negate => Ok(c_string_literal(x)),
+            _ => Err(format!("unsupported literal in constant")),
+        }
+    }
+
+    /// The name of a constant in the generated bindings.
+    pub fn renamed(name: &str, annotations: &AnnotationSet, config: &Config) -> String {
+        let rules = [annotations.parse_atom::<RenameRule>("rename-all"),
+                     config.constant.rename_constants];
+
+        if let Some(r) = find_first_some(&rules) {
+            // Constants are SCREAMING_SNAKE_CASE by convention
+            r.apply_to_snake_case(&name.to_lowercase(),
+                                  IdentifierType::Constant)
+        } else {
+            String::from(name)
+        }
+    }
+
+    pub fn apply_renaming(&mut self, config: &Config) {
+        self.name = Constant::renamed(&self.name, &self.annotations, config);
+    }
+
+    pub fn add_deps(&self, library: &Library, out: &mut DependencyGraph) {
+        self.ty.add_deps(library, out);
+    }
+
+    pub fn map_paths<F>(&self, f: &F) -> Constant
+        where F: Fn(&PathRef) -> PathRef
+    {
+        Constant {
+            ty: self.ty.map_paths(f),
+            ..self.clone()
+        }
+    }
+}
+impl Source for Constant {
+    fn write<F: Write>(&self, config: &Config, out: &mut SourceWriter<F>) {
+        self.documentation.write(config, out);
+
+        if config.language == Language::C {
+            out.write(&format!("#define {} {}", self.name, self.value));
+        } else {
+            out.write("static constexpr ");
+            cdecl::write_type(out, config, &self.ty, &self.name);
+            out.write(&format!(" = {};", self.value));
+        }
+    }
+}
+
+impl Conditional for Function {
+    fn cfg(&self) -> &Option<Cfg> { &self.cfg }
+}
+impl Conditional for Static {
+    fn cfg(&self) -> &Option<Cfg> { &self.cfg }
+}
+impl Conditional for Struct {
+    fn cfg(&self) -> &Option<Cfg> { &self.cfg }
+}
+impl Conditional for Union {
+    fn cfg(&self) -> &Option<Cfg> { &self.cfg }
+}
+impl Conditional for OpaqueStruct {
+    fn cfg(&self) -> &Option<Cfg> { &self.cfg }
+}
+impl Conditional for Enum {
+    fn cfg(&self) -> &Option<Cfg> { &self.cfg }
+}
+impl Conditional for Specialization {
+    fn cfg(&self) -> &Option<Cfg> { &self.cfg }
+}
+impl Conditional for Typedef {
+    fn cfg(&self) -> &Option<Cfg> { &self.cfg }
+}
+impl Conditional for Bitflags {
+    fn cfg(&self) -> &Option<Cfg> { &self.cfg }
+}
+impl Conditional for Constant {
+    fn cfg(&self) -> &Option<Cfg> { &self.cfg }
+}
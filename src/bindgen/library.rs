@@ -1,5 +1,8 @@
 use std::io::Write;
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::btree_map;
+use std::collections::HashMap;
 use std::collections::HashSet;
 use std::cmp::Ordering;
 use std::fs::File;
@@ -10,8 +13,15 @@ use syn;
 use bindgen::config;
 use bindgen::config::{Config, Language};
 use bindgen::annotation::*;
+use bindgen::bitflags;
+use bindgen::cfg::*;
+use bindgen::const_eval;
+use bindgen::const_eval::{ConstantScope, ConstantTable};
+use bindgen::documentation::Documentation;
 use bindgen::items::*;
 use bindgen::rust_lib;
+use bindgen::shims;
+use bindgen::shims::Shim;
 use bindgen::utilities::*;
 use bindgen::writer::{Source, SourceWriter};
 
@@ -26,15 +36,395 @@ pub enum Repr {
     U8,
     U16,
     U32,
+    U64,
+    USize,
+    I8,
+    I16,
+    I32,
+    I64,
+    ISize,
+}
+impl Repr {
+    /// The name of the rust integer type of this repr, if it has one.
+    pub fn type_name(&self) -> Option<&'static str> {
+        match *self {
+            Repr::None | Repr::C => None,
+            Repr::U8 => Some("u8"),
+            Repr::U16 => Some("u16"),
+            Repr::U32 => Some("u32"),
+            Repr::U64 => Some("u64"),
+            Repr::USize => Some("usize"),
+            Repr::I8 => Some("i8"),
+            Repr::I16 => Some("i16"),
+            Repr::I32 => Some("i32"),
+            Repr::I64 => Some("i64"),
+            Repr::ISize => Some("isize"),
+        }
+    }
+}
+
+/// The packing or alignment of a `repr(C)` struct.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Alignment {
+    Packed,
+    Align(u64),
 }
 
+/// A path to an item. Items in the library are keyed by their crate
+/// and module path, `crate::module::Name`, while the types of items
+/// refer to them by the path written in rust until they are resolved.
 pub type PathRef = String;
+
+/// The name of the item that a path refers to.
+pub fn path_name(path: &str) -> &str {
+    path.rsplit("::").next().unwrap()
+}
+
+/// The path of the module an item path is in.
+fn parent_path(path: &str) -> &str {
+    match path.rfind("::") {
+        Some(i) => &path[..i],
+        None => "",
+    }
+}
+
+/// The path of an item, or module, in a crate.
+fn item_path(crate_name: &str, mod_path: &[String], name: &str) -> PathRef {
+    let mut path = String::from(crate_name);
+    for segment in mod_path.iter().map(|x| x.as_str()).chain(Some(name)) {
+        if !segment.is_empty() {
+            path.push_str("::");
+            path.push_str(segment);
+        }
+    }
+    path
+}
+
+/// The indentation of a block of Rust source that is `level` blocks deep.
+fn indent(level: usize) -> String {
+    "    ".repeat(level)
+}
+
+/// Paths are only followed through imports this many times, to stop at cycles.
+const MAX_IMPORT_DEPTH: usize = 16;
+
+/// Generic items are only instantiated this many levels deep, to stop at
+/// polymorphic recursion, like a `next: *const List<*const T>` field of
+/// `List<T>`, which would need a new instance at every level.
+const MAX_INSTANTIATION_DEPTH: usize = 16;
+
+/// A module scope, used for resolving the paths written inside of it.
+#[derive(Debug, Clone)]
+struct Module {
+    crate_name: String,
+    path: Vec<String>,
+    /// The names brought into scope with `use`, and the path they import
+    imports: BTreeMap<String, PathRef>,
+    /// The paths of the modules imported with `use module::*`
+    globs: Vec<PathRef>,
+    /// The names of the structs, unions, enums and type aliases declared
+    /// in the module
+    types: BTreeSet<String>,
+    /// The types that can't be named outside of the module
+    private_types: BTreeSet<String>,
+}
+
+/// The modules of every parsed crate, keyed by their path.
+#[derive(Debug, Clone)]
+pub struct ModuleTree {
+    modules: BTreeMap<String, Module>,
+}
+
+impl ModuleTree {
+    fn new() -> ModuleTree {
+        ModuleTree {
+            modules: BTreeMap::new(),
+        }
+    }
+
+    fn insert(&mut self, module: Module) {
+        self.modules.insert(item_path(&module.crate_name, &module.path, ""), module);
+    }
+
+    /// Resolves a path written in the module at `mod_path` to an item for
+    /// which `has_item` is true, following the imports of the modules.
+    pub fn resolve<F>(&self, mod_path: &str, written: &str, has_item: &F) -> Option<PathRef>
+        where F: Fn(&str) -> bool
+    {
+        self.modules.get(mod_path).and_then(|module| {
+            self.resolve_in_module(module, written, false, 0, has_item)
+        })
+    }
+
+    /// Whether a path is a struct, union, enum or type alias of a module.
+    pub fn has_type(&self, path: &str) -> bool {
+        match path.rfind("::") {
+            Some(i) => {
+                self.modules.get(&path[..i])
+                            .map_or(false, |x| x.types.contains(&path[i + 2..]))
+            }
+            None => false,
+        }
+    }
+
+    /// Whether a path is to a type that only its module and the modules in
+    /// it can see. Every module can see the private items of a crate root.
+    pub fn has_private_type(&self, path: &str) -> bool {
+        match path.rfind("::") {
+            Some(i) => {
+                self.modules.get(&path[..i])
+                            .map_or(false, |x| !x.path.is_empty() && x.private_types.contains(&path[i + 2..]))
+            }
+            None => false,
+        }
+    }
+
+    /// The paths that a module imports with `use`, written from the crate
+    /// root so that they can be imported by another module.
+    fn use_paths(&self, crate_name: &str, mod_path: &[String]) -> Vec<String> {
+        let module = match self.modules.get(&item_path(crate_name, mod_path, "")) {
+            Some(module) => module,
+            None => return Vec::new(),
+        };
+
+        let mut result = module.globs.iter()
+                                     .map(|x| format!("{}::*", self.path_from_root(module, x)))
+                                     .collect::<Vec<_>>();
+        for (name, import) in &module.imports {
+            let path = self.path_from_root(module, import);
+            if path == *name || path.ends_with(&format!("::{}", name)) {
+                result.push(path);
+            } else {
+                result.push(format!("{} as {}", path, name));
+            }
+        }
+        result
+    }
+
+    /// Rewrites a path of a `use` declaration in a module to start at the
+    /// crate root, leaving paths into other crates as they are.
+    fn path_from_root(&self, module: &Module, written: &str) -> String {
+        let segments = written.split("::").collect::<Vec<_>>();
+
+        match segments[0] {
+            "" | "crate" => written.to_owned(),
+            "self" | "super" => {
+                let supers = segments.iter().take_while(|x| **x == "super").count();
+                let skip = if supers == 0 { 1 } else { supers };
+
+                let mut path = vec!["crate"];
+                path.extend(module.path.iter()
+                                       .take(module.path.len().saturating_sub(supers))
+                                       .map(|x| x.as_str()));
+                path.extend_from_slice(&segments[skip..]);
+                path.join("::")
+            }
+            first if self.modules.contains_key(&item_path(&module.crate_name, &[], first)) => {
+                format!("crate::{}", written)
+            }
+            _ => written.to_owned(),
+        }
+    }
+
+    /// Resolves a path written in a module. Paths in `use` declarations
+    /// start at the crate root, other paths start at the module itself.
+    fn resolve_in_module<F>(&self, module: &Module, written: &str, is_use: bool, depth: usize, has_item: &F) -> Option<PathRef>
+        where F: Fn(&str) -> bool
+    {
+        if depth > MAX_IMPORT_DEPTH {
+            warn!("too many imports while resolving {}", written);
+            return None;
+        }
+
+        let segments = written.split("::").collect::<Vec<_>>();
+
+        match segments[0] {
+            "" | "crate" => {
+                self.resolve_from_root(&module.crate_name, &segments[1..], depth, has_item)
+            }
+            "self" | "super" => {
+                let supers = segments.iter().take_while(|x| **x == "super").count();
+                if supers > module.path.len() {
+                    return None;
+                }
+
+                let mut path = module.path[..module.path.len() - supers].iter()
+                                                                        .map(|x| x.as_str())
+                                                                        .collect::<Vec<_>>();
+                let skip = if supers == 0 { 1 } else { supers };
+                path.extend_from_slice(&segments[skip..]);
+
+                self.lookup(&module.crate_name, &path, depth, has_item)
+            }
+            _ if is_use => {
+                self.resolve_from_root(&module.crate_name, &segments, depth, has_item)
+            }
+            _ => {
+                let mut path = module.path.iter()
+                                          .map(|x| x.as_str())
+                                          .collect::<Vec<_>>();
+                path.extend_from_slice(&segments);
+
+                self.lookup(&module.crate_name, &path, depth, has_item)
+                    .or_else(|| self.resolve_from_root(&module.crate_name, &segments, depth, has_item))
+            }
+        }
+    }
+
+    /// Resolves a path from the root of a crate, or from the root of
+    /// another crate if it starts with that crate's name.
+    fn resolve_from_root<F>(&self, crate_name: &str, segments: &[&str], depth: usize, has_item: &F) -> Option<PathRef>
+        where F: Fn(&str) -> bool
+    {
+        self.lookup(crate_name, segments, depth, has_item).or_else(|| {
+            match segments.split_first() {
+                Some((first, rest)) if !rest.is_empty() &&
+                                       *first != crate_name &&
+                                       self.modules.contains_key(*first) => {
+                    self.lookup(first, rest, depth, has_item)
+                }
+                _ => None,
+            }
+        })
+    }
+
+    /// Finds the item at a path from the root of a crate, following the
+    /// imports of the modules along the way.
+    fn lookup<F>(&self, crate_name: &str, segments: &[&str], depth: usize, has_item: &F) -> Option<PathRef>
+        where F: Fn(&str) -> bool
+    {
+        let mut mod_path = Vec::new();
+
+        for (i, segment) in segments.iter().enumerate() {
+            let module = match self.modules.get(&item_path(crate_name, &mod_path, "")) {
+                Some(module) => module,
+                None => return None,
+            };
+            let path = item_path(crate_name, &mod_path, segment);
+            let rest = &segments[i + 1..];
+
+            if rest.is_empty() {
+                if has_item(&path) {
+                    return Some(path);
+                }
+            } else if self.modules.contains_key(&path) {
+                mod_path.push(segment.to_string());
+                continue;
+            }
+
+            // Otherwise the name must have been imported into the module,
+            // either by name or by a glob
+            let rest = rest.iter()
+                           .map(|x| format!("::{}", x))
+                           .collect::<String>();
+
+            if let Some(import) = module.imports.get(*segment) {
+                return self.resolve_in_module(module, &format!("{}{}", import, rest), true, depth + 1, has_item);
+            }
+
+            return module.globs.iter().filter_map(|glob| {
+                let target = format!("{}::{}{}", glob, segment, rest);
+                self.resolve_in_module(module, &target, true, depth + 1, has_item)
+            }).next();
+        }
+
+        None
+    }
+}
+
+/// Items keyed by their path. A path has more than one definition when
+/// each definition is compiled under a different `#[cfg]`.
+#[derive(Debug, Clone)]
+struct ItemMap<T> {
+    data: BTreeMap<PathRef, Vec<T>>,
+}
+
+impl<T: Clone + Conditional> ItemMap<T> {
+    fn new() -> ItemMap<T> {
+        ItemMap {
+            data: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a definition of a path, replacing any definition with the same
+    /// cfg, which happens when a crate is parsed more than once.
+    fn insert(&mut self, path: PathRef, item: T) {
+        let definitions = self.data.entry(path).or_insert(Vec::new());
+
+        match definitions.iter().position(|x| x.cfg() == item.cfg()) {
+            Some(i) => definitions[i] = item,
+            None => definitions.push(item),
+        }
+    }
+
+    fn contains_key(&self, path: &str) -> bool {
+        self.data.contains_key(path)
+    }
+
+    fn get(&self, path: &str) -> &[T] {
+        match self.data.get(path) {
+            Some(definitions) => definitions,
+            None => &[],
+        }
+    }
+
+    fn keys<'b>(&'b self) -> btree_map::Keys<'b, PathRef, Vec<T>> {
+        self.data.keys()
+    }
+
+    /// Every definition of every path.
+    fn values<'b>(&'b self) -> impl Iterator<Item = &'b T> {
+        self.data.values().flat_map(|definitions| definitions.iter())
+    }
+
+    /// Adds the cfgs of the definitions of every path to `cfgs`.
+    fn collect_cfgs(&self, cfgs: &mut BTreeMap<PathRef, Vec<Option<Cfg>>>) {
+        for (path, definitions) in &self.data {
+            cfgs.entry(path.clone())
+                .or_insert(Vec::new())
+                .extend(definitions.iter().map(|x| x.cfg().clone()));
+        }
+    }
+
+    /// Removes all but the first definition of each path in `paths`. The
+    /// paths in `kept` already have a definition in another map, and those
+    /// that are kept in this one are added to it.
+    fn keep_first(&mut self, paths: &HashSet<PathRef>, kept: &mut HashSet<PathRef>) {
+        for (path, definitions) in self.data.iter_mut() {
+            if !paths.contains(path) {
+                continue;
+            }
+            if kept.contains(path) {
+                definitions.clear();
+            } else {
+                definitions.truncate(1);
+                kept.insert(path.clone());
+            }
+        }
+        self.data.retain(|_, definitions| !definitions.is_empty());
+    }
+
+    fn map<F>(&self, f: F) -> ItemMap<T>
+        where F: Fn(&PathRef, &T) -> T
+    {
+        ItemMap {
+            data: self.data.iter()
+                           .map(|(path, definitions)| {
+                               (path.clone(), definitions.iter().map(|x| f(path, x)).collect())
+                           })
+                           .collect(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum PathValue {
     Enum(Enum),
     Struct(Struct),
+    Union(Union),
     OpaqueStruct(OpaqueStruct),
     Typedef(Typedef),
+    Bitflags(Bitflags),
     Specialization(Specialization),
 }
 impl PathValue {
@@ -42,26 +432,106 @@ impl PathValue {
         match self {
             &PathValue::Enum(ref x) => { &x.name },
             &PathValue::Struct(ref x) => { &x.name },
+            &PathValue::Union(ref x) => { &x.name },
             &PathValue::OpaqueStruct(ref x) => { &x.name },
             &PathValue::Typedef(ref x) => { &x.name },
+            &PathValue::Bitflags(ref x) => { &x.name },
             &PathValue::Specialization(ref x) => { &x.name },
         }
     }
 
+    pub fn cfg(&self) -> &Option<Cfg> {
+        match self {
+            &PathValue::Enum(ref x) => { &x.cfg },
+            &PathValue::Struct(ref x) => { &x.cfg },
+            &PathValue::Union(ref x) => { &x.cfg },
+            &PathValue::OpaqueStruct(ref x) => { &x.cfg },
+            &PathValue::Typedef(ref x) => { &x.cfg },
+            &PathValue::Bitflags(ref x) => { &x.cfg },
+            &PathValue::Specialization(ref x) => { &x.cfg },
+        }
+    }
+
     pub fn add_deps(&self, library: &Library, out: &mut DependencyGraph) {
         match self {
-            &PathValue::Enum(_) => { },
+            &PathValue::Enum(ref x) => { x.add_deps(library, out); },
             &PathValue::Struct(ref x) => { x.add_deps(library, out); },
+            &PathValue::Union(ref x) => { x.add_deps(library, out); },
             &PathValue::OpaqueStruct(_) => { },
             &PathValue::Typedef(ref x) => { x.add_deps(library, out); },
+            &PathValue::Bitflags(_) => { },
             &PathValue::Specialization(ref x) => { x.add_deps(library, out); },
         }
     }
 
+    pub fn map_paths<F>(&self, f: &F) -> PathValue
+        where F: Fn(&PathRef) -> PathRef
+    {
+        match self {
+            &PathValue::Enum(ref x) => PathValue::Enum(x.map_paths(f)),
+            &PathValue::Struct(ref x) => PathValue::Struct(x.map_paths(f)),
+            &PathValue::Union(ref x) => PathValue::Union(x.map_paths(f)),
+            &PathValue::OpaqueStruct(ref x) => PathValue::OpaqueStruct(x.clone()),
+            &PathValue::Typedef(ref x) => PathValue::Typedef(x.map_paths(f)),
+            &PathValue::Bitflags(ref x) => PathValue::Bitflags(x.clone()),
+            &PathValue::Specialization(ref x) => PathValue::Specialization(x.map_paths(f)),
+        }
+    }
+
+    /// The items for a use of this generic item with `generic_values`.
+    pub fn instantiate(&self,
+                       name: &str,
+                       generic_values: &Vec<Type>,
+                       library: &Library,
+                       out: &DependencyGraph) -> ConvertResult<Vec<PathValue>> {
+        if out.instantiating.len() >= MAX_INSTANTIATION_DEPTH {
+            return Err(format!("instances are nested more than {} levels deep, starting at {}",
+                               MAX_INSTANTIATION_DEPTH,
+                               out.instantiating[0]));
+        }
+
+        match self {
+            &PathValue::Struct(ref x) => {
+                Ok(vec![PathValue::Struct(x.specialize(name, generic_values)?)])
+            }
+            &PathValue::Union(ref x) => {
+                Ok(vec![PathValue::Union(x.specialize(name, generic_values)?)])
+            }
+            &PathValue::OpaqueStruct(ref x) => {
+                Ok(vec![PathValue::OpaqueStruct(OpaqueStruct {
+                    name: String::from(name),
+                    generic_params: Vec::new(),
+                    ..x.clone()
+                })])
+            }
+            &PathValue::Specialization(ref x) => {
+                if generic_values.len() != x.generic_params.len() {
+                    return Err(format!("incomplete specialization"));
+                }
+
+                let mappings = x.generic_params.iter()
+                                               .zip(generic_values.iter())
+                                               .collect::<Vec<_>>();
+
+                Specialization {
+                    name: String::from(name),
+                    generic_params: Vec::new(),
+                    generic_values: x.generic_values.iter()
+                                                    .map(|x| x.specialize(&mappings))
+                                                    .collect(),
+                    ..x.clone()
+                }.specialize(library)
+            }
+            _ => Err(format!("{} doesn't have generic params", self.name())),
+        }
+    }
+
     pub fn apply_renaming(&mut self, config: &Config) {
         match self {
             &mut PathValue::Enum(ref mut x) => { x.apply_renaming(config); },
             &mut PathValue::Struct(ref mut x) => { x.apply_renaming(config); },
+            &mut PathValue::Union(ref mut x) => { x.apply_renaming(config); },
+            &mut PathValue::Bitflags(ref mut x) => { x.apply_renaming(config); },
             _ => { },
         }
     }
@@ -71,12 +541,23 @@ impl PathValue {
 pub struct DependencyGraph {
     order: Vec<PathValue>,
     items: HashSet<PathRef>,
+    /// The instances of generic items whose dependencies are being added
+    instantiating: Vec<PathRef>,
+    /// The paths of the instances of generic items, and the instances as
+    /// they are written in rust
+    instances: HashMap<PathRef, String>,
+    /// The instances of generic items that have the path of another item
+    /// that is used, and that path
+    collisions: Vec<(String, PathRef)>,
 }
 impl DependencyGraph {
     fn new() -> DependencyGraph {
         DependencyGraph {
             order: Vec::new(),
             items: HashSet::new(),
+            instantiating: Vec::new(),
+            instances: HashMap::new(),
+            collisions: Vec::new(),
         }
     }
 }
@@ -87,26 +568,51 @@ pub struct Library<'a> {
     bindings_crate_name: String,
     config: &'a Config,
 
-    enums: BTreeMap<String, Enum>,
-    structs: BTreeMap<String, Struct>,
-    opaque_structs: BTreeMap<String, OpaqueStruct>,
-    typedefs: BTreeMap<String, Typedef>,
-    specializations: BTreeMap<String, Specialization>,
-    functions: BTreeMap<String, Function>,
+    enums: ItemMap<Enum>,
+    structs: ItemMap<Struct>,
+    unions: ItemMap<Union>,
+    opaque_structs: ItemMap<OpaqueStruct>,
+    typedefs: ItemMap<Typedef>,
+    bitflags: ItemMap<Bitflags>,
+    specializations: ItemMap<Specialization>,
+    functions: ItemMap<Function>,
+    constants: ItemMap<Constant>,
+    globals: ItemMap<Static>,
+    /// The shims generated for `impl` blocks, in the order they were found
+    shims: Vec<Shim>,
+
+    modules: ModuleTree,
+    /// The cfgs of the modules that are compiled conditionally, which
+    /// apply to every item in them
+    module_cfgs: BTreeMap<String, Cfg>,
+    constant_values: ConstantTable,
 }
 
 impl<'a> Library<'a> {
+    pub fn config(&self) -> &'a Config {
+        self.config
+    }
+
     fn blank(bindings_crate_name: &str, config: &'a Config) -> Library<'a> {
         Library {
             bindings_crate_name: String::from(bindings_crate_name),
             config: config,
 
-            enums: BTreeMap::new(),
-            structs: BTreeMap::new(),
-            opaque_structs: BTreeMap::new(),
-            typedefs: BTreeMap::new(),
-            specializations: BTreeMap::new(),
-            functions: BTreeMap::new(),
+            enums: ItemMap::new(),
+            structs: ItemMap::new(),
+            unions: ItemMap::new(),
+            opaque_structs: ItemMap::new(),
+            typedefs: ItemMap::new(),
+            bitflags: ItemMap::new(),
+            specializations: ItemMap::new(),
+            functions: ItemMap::new(),
+            constants: ItemMap::new(),
+            globals: ItemMap::new(),
+            shims: Vec::new(),
+
+            modules: ModuleTree::new(),
+            module_cfgs: BTreeMap::new(),
+            constant_values: ConstantTable::new(),
         }
     }
 
@@ -115,10 +621,13 @@ impl<'a> Library<'a> {
     {
         let mut library = Library::blank("", config);
 
-        rust_lib::parse_src(src, &mut |crate_name, items| {
-            library.parse_crate_mod(&crate_name, items);
+        let mut modules = Vec::new();
+        rust_lib::parse_src(src, &mut |crate_name, mod_path, items| {
+            modules.push((crate_name.to_owned(), mod_path.to_owned(), items.clone()));
         })?;
 
+        library.parse_crate_mods(&modules);
+
         Ok(library)
     }
 
@@ -127,24 +636,116 @@ impl<'a> Library<'a> {
     {
         let mut library = Library::blank(bindings_crate_name, config);
 
+        let mut modules = Vec::new();
         rust_lib::parse_lib(crate_dir,
                             bindings_crate_name,
                             &config.expand,
-                            &mut |crate_name, items| {
-            library.parse_crate_mod(&crate_name, items);
+                            &mut |crate_name, mod_path, items| {
+            modules.push((crate_name.to_owned(), mod_path.to_owned(), items.clone()));
         })?;
 
+        library.parse_crate_mods(&modules);
+
         Ok(library)
     }
 
-    fn parse_crate_mod(&mut self, crate_name: &str, items: &Vec<syn::Item>) {
+    /// Parses the modules of every crate, given as their crate name, module
+    /// path and items, in the order that they were found. Every module is
+    /// collected first, so that paths can be resolved into any of them.
+    fn parse_crate_mods(&mut self, modules: &[(String, Vec<String>, Vec<syn::Item>)]) {
+        for &(ref crate_name, ref mod_path, ref items) in modules {
+            self.collect_module(crate_name, mod_path, items);
+        }
+        self.collect_constant_values(modules);
+
+        for &(ref crate_name, ref mod_path, ref items) in modules {
+            self.parse_crate_mod(crate_name, mod_path, items);
+        }
+
+        self.remove_unconditional_definitions();
+        self.resolve_paths();
+    }
+
+    /// Keeps only the first definition of the paths whose definitions
+    /// can't all be written under an `#if`, because one of their cfgs has
+    /// no define in the config. They would otherwise all be written.
+    fn remove_unconditional_definitions(&mut self) {
+        let mut types = BTreeMap::new();
+        self.enums.collect_cfgs(&mut types);
+        self.structs.collect_cfgs(&mut types);
+        self.unions.collect_cfgs(&mut types);
+        self.opaque_structs.collect_cfgs(&mut types);
+        self.typedefs.collect_cfgs(&mut types);
+        self.bitflags.collect_cfgs(&mut types);
+        self.specializations.collect_cfgs(&mut types);
+        let types = self.unconditional_paths(types);
+
+        let mut kept = HashSet::new();
+        self.enums.keep_first(&types, &mut kept);
+        self.structs.keep_first(&types, &mut kept);
+        self.unions.keep_first(&types, &mut kept);
+        self.opaque_structs.keep_first(&types, &mut kept);
+        self.typedefs.keep_first(&types, &mut kept);
+        self.bitflags.keep_first(&types, &mut kept);
+        self.specializations.keep_first(&types, &mut kept);
+
+        let mut values = BTreeMap::new();
+        self.functions.collect_cfgs(&mut values);
+        self.constants.collect_cfgs(&mut values);
+        self.globals.collect_cfgs(&mut values);
+        let values = self.unconditional_paths(values);
+
+        let mut kept = HashSet::new();
+        self.functions.keep_first(&values, &mut kept);
+        self.constants.keep_first(&values, &mut kept);
+        self.globals.keep_first(&values, &mut kept);
+    }
+
+    fn unconditional_paths(&self, cfgs: BTreeMap<PathRef, Vec<Option<Cfg>>>) -> HashSet<PathRef> {
+        let mut result = HashSet::new();
+
+        for (path, cfgs) in cfgs {
+            if cfgs.len() < 2 {
+                continue;
+            }
+            let missing = cfgs.iter()
+                              .filter_map(|x| x.as_ref())
+                              .filter_map(|x| x.condition(self.config).err())
+                              .next();
+
+            if let Some(key) = missing {
+                warn!("no define for `{}` in the config, only writing the first definition of {}", key, path);
+                result.insert(path);
+            }
+        }
+
+        result
+    }
+
+    fn parse_crate_mod(&mut self, crate_name: &str, mod_path: &[String], items: &Vec<syn::Item>) {
+        let constants = ConstantScope::new(&self.constant_values,
+                                           &self.modules,
+                                           item_path(crate_name, mod_path, ""));
+        let mod_cfg = self.module_cfgs.get(&item_path(crate_name, mod_path, "")).cloned();
+
         for item in items {
+            let path = item_path(crate_name, mod_path, &item.ident.to_string());
+            let cfg = Cfg::join(&mod_cfg, Cfg::load(&item.attrs));
+
             match item.node {
-                syn::ItemKind::ForeignMod(ref block) => {
-                    if !block.abi.is_c() {
-                        info!("skip {}::{} - non c abi extern block", crate_name, &item.ident);
-                        continue;
+                syn::ItemKind::Mod(_) => {
+                    if let Some(cfg) = cfg {
+                        self.module_cfgs.insert(path, cfg);
                     }
+                }
+                syn::ItemKind::ForeignMod(ref block) => {
+                    let abi = match block.abi.c_abi() {
+                        Some(abi) => abi,
+                        None => {
+                            info!("skip {}::{} - non c abi extern block", crate_name, &item.ident);
+                            continue;
+                        }
+                    };
 
                     for foreign_item in &block.items {
                         match foreign_item.node {
@@ -155,19 +756,14 @@ impl<'a> Library<'a> {
                                     continue;
                                 }
 
-                                let annotations = match AnnotationSet::parse(foreign_item.get_doc_attr()) {
-                                    Ok(x) => x,
-                                    Err(msg) => {
-                                        warn!("{}", msg);
-                                        AnnotationSet::new()
-                                    }
-                                };
+                                let cfg = Cfg::join(&cfg, Cfg::load(&foreign_item.attrs));
 
-                                match Function::convert(foreign_item.ident.to_string(), annotations, decl, true) {
+                                match Function::convert(foreign_item.ident.to_string(), cfg, ItemAttributes::load(foreign_item), decl, abi, true, &constants) {
                                     Ok(func) => {
                                         info!("take {}::{}", crate_name, &foreign_item.ident);
+                                        self.check_calling_convention(&func);
 
-                                        self.functions.insert(func.name.clone(), func);
+                                        self.functions.insert(item_path(crate_name, mod_path, &func.name), func);
                                     }
                                     Err(msg) => {
                                         info!("skip {}::{} - ({})", crate_name, &foreign_item.ident, msg);
@@ -189,24 +785,9 @@ impl<'a> Library<'a> {
                         continue;
                     }
 
-                    if item.is_no_mangle() && abi.is_c() {
-                        let annotations = match AnnotationSet::parse(item.get_doc_attr()) {
-                            Ok(x) => x,
-                            Err(msg) => {
-                                warn!("{}", msg);
-                                AnnotationSet::new()
-                            }
-                        };
-
-                        match Function::convert(item.ident.to_string(), annotations, decl, false) {
-                            Ok(func) => {
-                                info!("take {}::{}", crate_name, &item.ident);
-
-                                self.functions.insert(func.name.clone(), func);
-                            }
-                            Err(msg) => {
-                                info!("skip {}::{} - ({})", crate_name, &item.ident, msg);
-                            },
+                    if let (true, Some(abi)) = (item.is_no_mangle(), abi.c_abi()) {
+                        if let Some(func) = self.parse_function(crate_name, cfg, item, decl, abi, &constants) {
+                            self.functions.insert(path, func);
                         }
                     } else {
                         if item.is_no_mangle() != abi.is_c() {
@@ -214,9 +795,179 @@ impl<'a> Library<'a> {
                         }
                     }
                 }
+                syn::ItemKind::Impl(..) => {
+                    let annotations = match AnnotationSet::parse(item.get_doc_attr()) {
+                        Ok(x) => x,
+                        Err(msg) => {
+                            warn!("{}", msg);
+                            AnnotationSet::new()
+                        }
+                    };
+                    let prefix = match annotations.atom("ffi-prefix") {
+                        Some(Some(prefix)) => prefix,
+                        _ => continue,
+                    };
+
+                    if crate_name != self.bindings_crate_name {
+                        info!("skip {}::{} - (impl's outside of the binding crate are not used)", crate_name, mod_path.join("::"));
+                        continue;
+                    }
+
+                    let generated = match shims::generate(&prefix, mod_path, item, &constants) {
+                        Ok(x) => x,
+                        Err(msg) => {
+                            warn!("skipping ffi-prefix={} impl in {}::{} - ({})", prefix, crate_name, mod_path.join("::"), msg);
+                            continue;
+                        }
+                    };
+
+                    for shim in generated {
+                        if let syn::ItemKind::Fn(ref decl, _, _, _, _, _) = shim.item.node {
+                            let path = item_path(crate_name, mod_path, &shim.item.ident.to_string());
+                            if let Some(func) = self.parse_function(crate_name, cfg.clone(), &shim.item, decl, Abi::C, &constants) {
+                                self.functions.insert(path, func);
+                            }
+                        }
+                        self.shims.push(shim);
+                    }
+                }
+                syn::ItemKind::Const(ref ty, ref expr) => {
+                    if crate_name != self.bindings_crate_name {
+                        info!("skip {}::{} - (const's outside of the binding crate are not used)", crate_name, &item.ident);
+                        continue;
+                    }
+
+                    if item.vis != syn::Visibility::Public {
+                        info!("skip {}::{} - (const is not public)", crate_name, &item.ident);
+                        continue;
+                    }
+
+                    let annotations = match AnnotationSet::parse(item.get_doc_attr()) {
+                        Ok(x) => x,
+                        Err(msg) => {
+                            warn!("{}", msg);
+                            AnnotationSet::new()
+                        }
+                    };
+                    let documentation = Documentation::load(&item.attrs);
+
+                    match Constant::convert(item.ident.to_string(),
+                                            cfg,
+                                            annotations,
+                                            documentation,
+                                            ty,
+                                            expr,
+                                            &constants) {
+                        Ok(constant) => {
+                            info!("take {}::{}", crate_name, &item.ident);
+
+                            self.constants.insert(path, constant);
+                        }
+                        Err(msg) => {
+                            info!("skip {}::{} - ({})", crate_name, &item.ident, msg);
+                        }
+                    }
+                }
+                syn::ItemKind::Static(ref ty, ref mutability, ref _expr) => {
+                    if crate_name != self.bindings_crate_name {
+                        info!("skip {}::{} - (static's outside of the binding crate are not used)", crate_name, &item.ident);
+                        continue;
+                    }
+
+                    if !item.is_no_mangle() {
+                        info!("skip {}::{} - (static is not marked no_mangle)", crate_name, &item.ident);
+                        continue;
+                    }
+
+                    let documentation = Documentation::load(&item.attrs);
+
+                    match Static::convert(item.ident.to_string(),
+                                          cfg,
+                                          documentation,
+                                          ty,
+                                          *mutability,
+                                          &constants) {
+                        Ok(global) => {
+                            info!("take {}::{}", crate_name, &item.ident);
+
+                            self.globals.insert(path, global);
+                        }
+                        Err(msg) => {
+                            info!("skip {}::{} - ({})", crate_name, &item.ident, msg);
+                        }
+                    }
+                }
                 syn::ItemKind::Struct(ref variant,
                                       ref generics) => {
                     let struct_name = item.ident.to_string();
+                    let attributes = ItemAttributes::load(item);
+                    let annotations = attributes.annotations.clone();
+                    let documentation = attributes.documentation.clone();
+
+                    if item.is_repr_c() {
+                        let converted = item.get_alignment().and_then(|alignment| {
+                            Struct::convert(struct_name.clone(),
+                                            cfg.clone(),
+                                            attributes,
+                                            alignment,
+                                            variant,
+                                            generics,
+                                            &constants)
+                        });
+
+                        match converted {
+                            Ok(st) => {
+                                info!("take {}::{}", crate_name, &item.ident);
+                                self.structs.insert(path, st);
+                            }
+                            Err(msg) => {
+                                info!("take {}::{} - opaque ({})", crate_name, &item.ident, msg);
+                                self.opaque_structs.insert(path, OpaqueStruct::new(struct_name, generics, cfg, annotations, documentation));
+                            }
+                        }
+                    } else if item.is_repr_transparent() {
+                        match Struct::convert(struct_name.clone(),
+                                              cfg.clone(),
+                                              attributes,
+                                              None,
+                                              variant,
+                                              generics,
+                                              &constants) {
+                            Ok(ref st) if st.fields.len() != 1 => {
+                                info!("take {}::{} - opaque (transparent struct without exactly one non zero sized field)", crate_name, &item.ident);
+                                self.opaque_structs.insert(path, OpaqueStruct::new(struct_name, generics, cfg, annotations, documentation));
+                            }
+                            Ok(st) => {
+                                info!("take {}::{}", crate_name, &item.ident);
+
+                                // A typedef can't be generic, so generic transparent
+                                // structs are kept as structs to be specialized
+                                if self.config.structure.transparent_as_struct ||
+                                   !st.generic_params.is_empty() {
+                                    self.structs.insert(path, st);
+                                } else {
+                                    self.typedefs.insert(path, Typedef {
+                                        name: struct_name,
+                                        cfg: cfg,
+                                        annotations: annotations,
+                                        documentation: documentation,
+                                        aliased: st.fields[0].1.clone(),
+                                    });
+                                }
+                            }
+                            Err(msg) => {
+                                info!("take {}::{} - opaque ({})", crate_name, &item.ident, msg);
+                                self.opaque_structs.insert(path, OpaqueStruct::new(struct_name, generics, cfg, annotations, documentation));
+                            }
+                        }
+                    } else {
+                        info!("take {}::{} - opaque (not marked as repr(C) or repr(transparent))", crate_name, &item.ident);
+                        self.opaque_structs.insert(path, OpaqueStruct::new(struct_name, generics, cfg, annotations, documentation));
+                    }
+                }
+                syn::ItemKind::Union(ref variant,
+                                     ref generics) => {
+                    let union_name = item.ident.to_string();
                     let annotations = match AnnotationSet::parse(item.get_doc_attr()) {
                         Ok(x) => x,
                         Err(msg) => {
@@ -224,52 +975,57 @@ impl<'a> Library<'a> {
                             AnnotationSet::new()
                         }
                     };
+                    let documentation = Documentation::load(&item.attrs);
 
                     if item.is_repr_c() {
-                        match Struct::convert(struct_name.clone(), annotations.clone(), variant, generics) {
-                            Ok(st) => {
+                        match Union::convert(union_name.clone(),
+                                             cfg.clone(),
+                                             annotations.clone(),
+                                             documentation.clone(),
+                                             variant,
+                                             generics,
+                                             &constants) {
+                            Ok(u) => {
                                 info!("take {}::{}", crate_name, &item.ident);
-                                self.structs.insert(struct_name,
-                                                    st);
+                                self.unions.insert(path, u);
                             }
                             Err(msg) => {
                                 info!("take {}::{} - opaque ({})", crate_name, &item.ident, msg);
-                                self.opaque_structs.insert(struct_name.clone(),
-                                                           OpaqueStruct::new(struct_name, annotations));
+                                self.opaque_structs.insert(path, OpaqueStruct::new(union_name, generics, cfg, annotations, documentation));
                             }
                         }
                     } else {
                         info!("take {}::{} - opaque (not marked as repr(C))", crate_name, &item.ident);
-                        self.opaque_structs.insert(struct_name.clone(),
-                                                   OpaqueStruct::new(struct_name, annotations));
+                        self.opaque_structs.insert(path, OpaqueStruct::new(union_name, generics, cfg, annotations, documentation));
                     }
                 }
                 syn::ItemKind::Enum(ref variants, ref generics) => {
-                    if !generics.lifetimes.is_empty() ||
-                       !generics.ty_params.is_empty() ||
+                    // Lifetimes don't affect the layout, so they are ignored
+                    if !generics.ty_params.is_empty() ||
                        !generics.where_clause.predicates.is_empty() {
-                        info!("skip {}::{} - (has generics or lifetimes or where bounds)", crate_name, &item.ident);
+                        info!("skip {}::{} - (has generics or where bounds)", crate_name, &item.ident);
                         continue;
                     }
 
                     let enum_name = item.ident.to_string();
-                    let annotations = match AnnotationSet::parse(item.get_doc_attr()) {
-                        Ok(x) => x,
-                        Err(msg) => {
-                            warn!("{}", msg);
-                            AnnotationSet::new()
-                        }
-                    };
+                    let attributes = ItemAttributes::load(item);
+                    let annotations = attributes.annotations.clone();
+                    let documentation = attributes.documentation.clone();
 
-                    match Enum::convert(enum_name.clone(), item.get_repr(), annotations.clone(), variants) {
+                    match Enum::convert(enum_name.clone(),
+                                        cfg.clone(),
+                                        item.get_repr(),
+                                        item.is_repr_c(),
+                                        attributes,
+                                        variants,
+                                        &constants) {
                         Ok(en) => {
                             info!("take {}::{}", crate_name, &item.ident);
-                            self.enums.insert(enum_name, en);
+                            self.enums.insert(path, en);
                         }
                         Err(msg) => {
                             info!("take {}::{} - opaque ({})", crate_name, &item.ident, msg);
-                            self.opaque_structs.insert(enum_name.clone(),
-                                                       OpaqueStruct::new(enum_name, annotations));
+                            self.opaque_structs.insert(path, OpaqueStruct::new(enum_name, generics, cfg, annotations, documentation));
                         }
                     }
                 }
@@ -282,79 +1038,430 @@ impl<'a> Library<'a> {
                             AnnotationSet::new()
                         }
                     };
+                    let documentation = Documentation::load(&item.attrs);
 
                     let fail1 = match Specialization::convert(alias_name.clone(),
+                                                              cfg.clone(),
                                                               annotations.clone(),
+                                                              documentation.clone(),
                                                               generics,
-                                                              ty) {
+                                                              ty,
+                                                              &constants) {
                         Ok(spec) => {
                             info!("take {}::{}", crate_name, &item.ident);
-                            self.specializations.insert(alias_name, spec);
+                            self.specializations.insert(path, spec);
                             continue;
                         }
                         Err(msg) => msg,
                     };
 
-                    if !generics.lifetimes.is_empty() ||
-                       !generics.ty_params.is_empty() {
-                        info!("skip {}::{} - (typedefs cannot have generics or lifetimes)", crate_name, &item.ident);
+                    if !generics.ty_params.is_empty() {
+                        info!("skip {}::{} - (typedefs cannot have generics)", crate_name, &item.ident);
                         continue;
                     }
 
-                    let fail2 = match Typedef::convert(alias_name.clone(), annotations, ty) {
+                    let fail2 = match Typedef::convert(alias_name.clone(), cfg, annotations, documentation, ty, &constants) {
                         Ok(typedef) => {
                             info!("take {}::{}", crate_name, &item.ident);
-                            self.typedefs.insert(alias_name, typedef);
+                            self.typedefs.insert(path, typedef);
                             continue;
                         }
                         Err(msg) => msg,
                     };
                     info!("skip {}::{} - ({} and {})", crate_name, &item.ident, fail1, fail2);
                 }
+                syn::ItemKind::Mac(ref mac) if bitflags::is_bitflags(mac) => {
+                    let decls = match bitflags::parse(mac) {
+                        Ok(x) => x,
+                        Err(msg) => {
+                            warn!("skipping bitflags! invocation in {}::{} - ({})", crate_name, mod_path.join("::"), msg);
+                            continue;
+                        }
+                    };
+
+                    for decl in decls {
+                        let path = item_path(crate_name, mod_path, &decl.item.ident.to_string());
+                        let cfg = Cfg::join(&cfg, Cfg::load(&decl.item.attrs));
+                        let annotations = match AnnotationSet::parse(decl.item.get_doc_attr()) {
+                            Ok(x) => x,
+                            Err(msg) => {
+                                warn!("{}", msg);
+                                AnnotationSet::new()
+                            }
+                        };
+                        let documentation = Documentation::load(&decl.item.attrs);
+
+                        match Bitflags::convert(decl.item.ident.to_string(),
+                                                cfg,
+                                                annotations,
+                                                documentation,
+                                                &decl.ty,
+                                                &decl.flags,
+                                                &constants) {
+                            Ok(flags) => {
+                                info!("take {}::{}", crate_name, &decl.item.ident);
+                                self.bitflags.insert(path, flags);
+                            }
+                            Err(msg) => {
+                                info!("skip {}::{} - ({})", crate_name, &decl.item.ident, msg);
+                            }
+                        }
+                    }
+                }
                 _ => {}
             }
         }
     }
 
-    pub fn resolve_path(&self, p: &PathRef) -> Option<PathValue> {
-        if let Some(x) = self.enums.get(p) {
-            return Some(PathValue::Enum(x.clone()));
+    fn parse_function(&self,
+                      crate_name: &str,
+                      cfg: Option<Cfg>,
+                      item: &syn::Item,
+                      decl: &syn::FnDecl,
+                      abi: Abi,
+                      constants: &ConstantScope) -> Option<Function> {
+        match Function::convert(item.ident.to_string(), cfg, ItemAttributes::load(item), decl, abi, false, constants) {
+            Ok(func) => {
+                info!("take {}::{}", crate_name, &item.ident);
+                self.check_calling_convention(&func);
+
+                Some(func)
+            }
+            Err(msg) => {
+                info!("skip {}::{} - ({})", crate_name, &item.ident, msg);
+                None
+            },
         }
-        if let Some(x) = self.structs.get(p) {
-            return Some(PathValue::Struct(x.clone()));
+    }
+
+    /// Functions with a calling convention other than C's are declared with
+    /// the macro for it in the config.
+    fn check_calling_convention(&self, func: &Function) {
+        if func.abi != Abi::C && self.config.function.calling_convention(func.abi).is_none() {
+            warn!("no calling convention macro for `extern \"{}\"` in the config, declaring {} without one",
+                  func.abi.name(),
+                  func.name);
         }
-        if let Some(x) = self.opaque_structs.get(p) {
-            return Some(PathValue::OpaqueStruct(x.clone()));
+    }
+
+    /// Evaluates the integer `const` items of every module, so they can be
+    /// referenced by enum discriminants and other constant expressions.
+    fn collect_constant_values(&mut self, modules: &[(String, Vec<String>, Vec<syn::Item>)]) {
+        let mut pending = Vec::new();
+
+        for &(ref crate_name, ref mod_path, ref items) in modules {
+            for item in items {
+                if let syn::ItemKind::Const(ref ty, ref expr) = item.node {
+                    self.constant_values.declare(item_path(crate_name, mod_path, &item.ident.to_string()));
+                    pending.push((crate_name, mod_path, item, ty, expr));
+                }
+            }
         }
-        if let Some(x) = self.typedefs.get(p) {
-            return Some(PathValue::Typedef(x.clone()));
+
+        // Constants can refer to constants defined later in the crate, so
+        // keep evaluating until nothing else can be resolved
+        loop {
+            let before = pending.len();
+            let mut unresolved = Vec::new();
+            let mut resolved = Vec::new();
+
+            for (crate_name, mod_path, item, ty, expr) in pending {
+                let ty_name = match **ty {
+                    syn::Ty::Path(None, ref path) if path.segments.len() == 1 => {
+                        path.segments[0].ident.to_string()
+                    }
+                    _ => String::new(),
+                };
+                let constants = ConstantScope::new(&self.constant_values,
+                                                   &self.modules,
+                                                   item_path(crate_name, mod_path, ""));
+
+                match const_eval::evaluate_expr(expr, Some(&ty_name), &constants) {
+                    Ok(value) => resolved.push((crate_name, mod_path, item, value)),
+                    Err(_) => unresolved.push((crate_name, mod_path, item, ty, expr)),
+                }
+            }
+
+            for (crate_name, mod_path, item, value) in resolved {
+                let name = item.ident.to_string();
+                let path = item_path(crate_name, mod_path, &name);
+                self.constant_values.insert(path.clone(), value);
+
+                // Exported constants can be used by name in array lengths
+                if self.config.constant.use_in_array_lengths &&
+                   *crate_name == self.bindings_crate_name &&
+                   item.vis == syn::Visibility::Public {
+                    let annotations = AnnotationSet::parse(item.get_doc_attr())
+                                                    .unwrap_or(AnnotationSet::new());
+                    let exported_name = Constant::renamed(&name, &annotations, self.config);
+
+                    self.constant_values.insert_exported_name(path, exported_name);
+                }
+            }
+
+            pending = unresolved;
+            if pending.is_empty() || pending.len() == before {
+                break;
+            }
         }
-        if let Some(x) = self.specializations.get(p) {
-            return Some(PathValue::Specialization(x.clone()));
+    }
+
+    /// Records a module, and the names that its `use` declarations bring
+    /// into scope. Re-exports are `use` declarations as well, and are
+    /// followed when resolving a path through the module.
+    fn collect_module(&mut self, crate_name: &str, mod_path: &[String], items: &Vec<syn::Item>) {
+        let mut imports = BTreeMap::new();
+        let mut globs = Vec::new();
+        let mut types = BTreeSet::new();
+        let mut private_types = BTreeSet::new();
+
+        for item in items {
+            match item.node {
+                syn::ItemKind::Struct(..) |
+                syn::ItemKind::Union(..) |
+                syn::ItemKind::Enum(..) |
+                syn::ItemKind::Ty(..) => {
+                    types.insert(item.ident.to_string());
+                    if item.vis != syn::Visibility::Public && item.vis != syn::Visibility::Crate {
+                        private_types.insert(item.ident.to_string());
+                    }
+                }
+                _ => {}
+            }
+
+            if let syn::ItemKind::Use(ref view_path) = item.node {
+                match **view_path {
+                    syn::ViewPath::Simple(ref path, ref rename) => {
+                        let name = match (rename, path.segments.last()) {
+                            (&Some(ref rename), _) => rename.to_string(),
+                            (&None, Some(last)) => last.ident.to_string(),
+                            (&None, None) => continue,
+                        };
+
+                        imports.insert(name, path.as_path_ref());
+                    }
+                    syn::ViewPath::Glob(ref path) => {
+                        globs.push(path.as_path_ref());
+                    }
+                    syn::ViewPath::List(ref path, ref list) => {
+                        let prefix = path.as_path_ref();
+
+                        for import in list {
+                            let (name, import_path) = if import.name == "self" {
+                                match path.segments.last() {
+                                    Some(last) => (last.ident.to_string(), prefix.clone()),
+                                    None => continue,
+                                }
+                            } else if prefix.is_empty() {
+                                (import.name.to_string(), import.name.to_string())
+                            } else {
+                                (import.name.to_string(), format!("{}::{}", prefix, import.name))
+                            };
+
+                            match import.rename {
+                                Some(ref rename) => imports.insert(rename.to_string(), import_path),
+                                None => imports.insert(name, import_path),
+                            };
+                        }
+                    }
+                }
+            }
         }
 
-        None
+        self.modules.insert(Module {
+            crate_name: String::from(crate_name),
+            path: mod_path.to_owned(),
+            imports: imports,
+            globs: globs,
+            types: types,
+            private_types: private_types,
+        });
+    }
+
+    /// Resolves the paths written in the types of every item to the
+    /// items they refer to, now that all the modules have been parsed.
+    fn resolve_paths(&mut self) {
+        let enums = self.enums.map(|path, x| {
+            x.map_paths(&|p| self.resolve_written_path(path, p))
+        });
+        let structs = self.structs.map(|path, x| {
+            x.map_paths(&|p| self.resolve_written_path(path, p))
+        });
+        let unions = self.unions.map(|path, x| {
+            x.map_paths(&|p| self.resolve_written_path(path, p))
+        });
+        let typedefs = self.typedefs.map(|path, x| {
+            x.map_paths(&|p| self.resolve_written_path(path, p))
+        });
+        let specializations = self.specializations.map(|path, x| {
+            x.map_paths(&|p| self.resolve_written_path(path, p))
+        });
+        let functions = self.functions.map(|path, x| {
+            x.map_paths(&|p| self.resolve_written_path(path, p))
+        });
+        let constants = self.constants.map(|path, x| {
+            x.map_paths(&|p| self.resolve_written_path(path, p))
+        });
+        let globals = self.globals.map(|path, x| {
+            x.map_paths(&|p| self.resolve_written_path(path, p))
+        });
+
+        self.enums = enums;
+        self.structs = structs;
+        self.unions = unions;
+        self.typedefs = typedefs;
+        self.specializations = specializations;
+        self.functions = functions;
+        self.constants = constants;
+        self.globals = globals;
+    }
+
+    /// Resolves a path written in the item at `item`, keeping it as
+    /// written if it doesn't refer to any known item.
+    fn resolve_written_path(&self, item: &str, written: &PathRef) -> PathRef {
+        let resolved = self.modules.resolve(parent_path(item), written, &|p| self.has_item(p));
+
+        resolved.or_else(|| self.find_by_name(written))
+                .unwrap_or_else(|| written.clone())
+    }
+
+    /// Finds an item by its name alone, for paths that can't be resolved
+    /// through the parsed modules. This only succeeds if the name is unique.
+    fn find_by_name(&self, name: &str) -> Option<PathRef> {
+        if name.contains("::") {
+            return None;
+        }
+
+        let mut found = self.enums.keys()
+                                  .chain(self.structs.keys())
+                                  .chain(self.unions.keys())
+                                  .chain(self.opaque_structs.keys())
+                                  .chain(self.typedefs.keys())
+                                  .chain(self.bitflags.keys())
+                                  .chain(self.specializations.keys())
+                                  .filter(|x| path_name(x) == name);
+
+        match (found.next(), found.next()) {
+            (Some(path), None) => Some(path.clone()),
+            _ => None,
+        }
+    }
+
+    fn has_item(&self, p: &str) -> bool {
+        self.enums.contains_key(p) ||
+        self.structs.contains_key(p) ||
+        self.unions.contains_key(p) ||
+        self.opaque_structs.contains_key(p) ||
+        self.typedefs.contains_key(p) ||
+        self.bitflags.contains_key(p) ||
+        self.specializations.contains_key(p)
+    }
+
+    /// Every definition of the item at a path. Definitions under
+    /// different cfgs can be of different kinds of items.
+    pub fn resolve_path(&self, p: &PathRef) -> Vec<PathValue> {
+        let mut result = Vec::new();
+
+        result.extend(self.enums.get(p).iter().map(|x| PathValue::Enum(x.clone())));
+        result.extend(self.structs.get(p).iter().map(|x| PathValue::Struct(x.clone())));
+        result.extend(self.unions.get(p).iter().map(|x| PathValue::Union(x.clone())));
+        result.extend(self.opaque_structs.get(p).iter().map(|x| PathValue::OpaqueStruct(x.clone())));
+        result.extend(self.typedefs.get(p).iter().map(|x| PathValue::Typedef(x.clone())));
+        result.extend(self.bitflags.get(p).iter().map(|x| PathValue::Bitflags(x.clone())));
+        result.extend(self.specializations.get(p).iter().map(|x| PathValue::Specialization(x.clone())));
+
+        result
     }
 
     pub fn add_deps_for_path(&self, p: &PathRef, out: &mut DependencyGraph) {
-        if let Some(value) = self.resolve_path(p) {
-            if !out.items.contains(p) {
-                out.items.insert(p.clone());
+        let values = self.resolve_path(p);
+
+        if values.is_empty() {
+            warn!("can't find {}", p);
+            return;
+        }
+
+        if let Some(instance) = out.instances.get(p).cloned() {
+            if !self.is_alias_of(p, path_name(p)) {
+                out.collisions.push((instance, p.clone()));
+            }
+        }
 
+        if !out.items.contains(p) {
+            out.items.insert(p.clone());
+
+            for value in values {
                 value.add_deps(self, out);
 
                 out.order.push(value);
             }
-        } else {
-            warn!("can't find {}", p);
         }
     }
 
-    pub fn add_deps_for_path_deps(&self, p: &PathRef, out: &mut DependencyGraph) {
-        if let Some(value) = self.resolve_path(p) {
-            value.add_deps(self, out);
-        } else {
+    /// Whether the item at a path is only aliases of the instance of a
+    /// generic item that is named `name`.
+    fn is_alias_of(&self, p: &PathRef, name: &str) -> bool {
+        self.resolve_path(p).iter().all(|value| {
+            match value {
+                &PathValue::Specialization(ref x) => {
+                    mangle_path(&x.aliased, &x.generic_values, self.config) == name
+                }
+                _ => false,
+            }
+        })
+    }
+
+    /// Adds the item generated for a use of a generic item, such as a
+    /// `Foo_i32` struct for `Foo<i32>`.
+    pub fn add_deps_for_generic_path(&self, p: &PathRef, generics: &Vec<Type>, out: &mut DependencyGraph) {
+        let name = mangle_path(p, generics, self.config);
+        let path = format!("{}::{}", parent_path(p), name);
+
+        let values = self.resolve_path(p);
+
+        if values.is_empty() {
             warn!("can't find {}", p);
+            return;
+        }
+
+        // An item that is used can have the path of the instance, unless
+        // it is an alias of the instance itself
+        let args = generics.iter()
+                           .map(|x| x.mangle(self.config))
+                           .collect::<Vec<_>>();
+        let instance = format!("{}<{}>", p, args.join(", "));
+        if out.items.contains(&path) && !out.instances.contains_key(&path) &&
+           !self.is_alias_of(&path, &name) {
+            out.collisions.push((instance.clone(), path.clone()));
+        }
+
+        if !out.items.contains(&path) {
+            out.items.insert(path.clone());
+            out.instances.insert(path.clone(), instance);
+
+            for value in values {
+                match value.instantiate(&name, generics, self, out) {
+                    Ok(instances) => {
+                        out.instantiating.push(path.clone());
+                        for instance in instances {
+                            instance.add_deps(self, out);
+
+                            out.order.push(instance);
+                        }
+                        out.instantiating.pop();
+                    }
+                    Err(msg) => {
+                        warn!("can't generate {} for {} - ({})", name, p, msg);
+
+                        // It is still declared, for the uses behind a pointer
+                        out.order.push(PathValue::OpaqueStruct(OpaqueStruct::new(name.clone(),
+                                                                                 &syn::Generics::default(),
+                                                                                 value.cfg().clone(),
+                                                                                 AnnotationSet::new(),
+                                                                                 Documentation::none())));
+                    }
+                }
+            }
         }
     }
 
@@ -365,9 +1472,42 @@ impl<'a> Library<'a> {
         // Gather only the items that we need for this
         // `extern "c"` interface
         let mut deps = DependencyGraph::new();
-        for (_, function) in &self.functions {
+        for function in self.functions.values() {
             function.add_deps(&self, &mut deps);
         }
+        for constant in self.constants.values() {
+            constant.add_deps(&self, &mut deps);
+        }
+        for global in self.globals.values() {
+            global.add_deps(&self, &mut deps);
+        }
+
+        // Items are named by their identifier alone in C, so items with the
+        // same name from different modules or crates can't both be emitted.
+        // Definitions of the same path under different cfgs are fine.
+        let mut paths = deps.items.iter()
+                                  .chain(self.functions.keys())
+                                  .chain(self.constants.keys())
+                                  .chain(self.globals.keys())
+                                  .collect::<Vec<_>>();
+        paths.sort();
+
+        if let Some(&(ref instance, ref path)) = deps.collisions.first() {
+            return Err(format!("`{}` and `{}` would both be named `{}` in the bindings",
+                               instance,
+                               path,
+                               path_name(path)));
+        }
+
+        let mut names = HashMap::new();
+        for path in paths {
+            if let Some(other) = names.insert(path_name(path), path) {
+                return Err(format!("`{}` and `{}` would both be named `{}` in the bindings",
+                                   other,
+                                   path,
+                                   path_name(path)));
+            }
+        }
 
         // Copy the binding items in dependencies order
         // into the BuiltBindings, specializing any type
@@ -375,16 +1515,27 @@ impl<'a> Library<'a> {
         for dep in deps.order {
             match &dep {
                 &PathValue::Struct(ref s) => {
-                    if !s.generic_params.is_empty() {
+                    if !s.generic_params.is_empty() && !self.config.generics_as_templates() {
+                        continue;
+                    }
+                }
+                &PathValue::Union(ref u) => {
+                    if !u.generic_params.is_empty() && !self.config.generics_as_templates() {
+                        continue;
+                    }
+                }
+                &PathValue::OpaqueStruct(ref o) => {
+                    if !o.generic_params.is_empty() && !self.config.generics_as_templates() {
                         continue;
                     }
                 }
+                // Templates are aliased with `using`, instead of being specialized
+                &PathValue::Specialization(_) if self.config.generics_as_templates() => { }
                 &PathValue::Specialization(ref s) => {
-                    match s.specialize(self.config, &self) {
-                        Ok(Some(value)) => {
-                            result.items.push(value);
+                    match s.specialize(&self) {
+                        Ok(values) => {
+                            result.items.extend(values);
                         }
-                        Ok(None) => {},
                         Err(msg) => {
                             warn!("specializing {} failed - ({})", dep.name(), msg);
                         }
@@ -397,12 +1548,14 @@ impl<'a> Library<'a> {
         }
 
         // Sort enums and opaque structs into their own layers because they don't
-        // depend on each other or anything else.
+        // depend on each other or anything else. Enums with fields are tagged
+        // unions which can depend on other types, so they stay in place.
         let ordering = |a: &PathValue, b: &PathValue| {
             match (a, b) {
-                (&PathValue::Enum(ref e1), &PathValue::Enum(ref e2)) => e1.name.cmp(&e2.name),
-                (&PathValue::Enum(_), _) => Ordering::Less,
-                (_, &PathValue::Enum(_)) => Ordering::Greater,
+                (&PathValue::Enum(ref e1), &PathValue::Enum(ref e2))
+                    if !e1.has_fields() && !e2.has_fields() => e1.name.cmp(&e2.name),
+                (&PathValue::Enum(ref e), _) if !e.has_fields() => Ordering::Less,
+                (_, &PathValue::Enum(ref e)) if !e.has_fields() => Ordering::Greater,
 
                 (&PathValue::OpaqueStruct(ref o1), &PathValue::OpaqueStruct(ref o2)) => o1.name.cmp(&o2.name),
                 (&PathValue::OpaqueStruct(_), _) => Ordering::Less,
@@ -413,9 +1566,21 @@ impl<'a> Library<'a> {
         };
         result.items.sort_by(ordering);
 
-        result.functions = self.functions.iter()
-                                         .map(|(_, function)| function.clone())
+        // Refer to items by their C name from now on
+        let c_name = |p: &PathRef| String::from(path_name(p));
+
+        result.items = result.items.iter()
+                                   .map(|item| item.map_paths(&c_name))
+                                   .collect::<Vec<_>>();
+        result.functions = self.functions.values()
+                                         .map(|function| function.map_paths(&c_name))
+                                         .collect::<Vec<_>>();
+        result.constants = self.constants.values()
+                                         .map(|constant| constant.map_paths(&c_name))
                                          .collect::<Vec<_>>();
+        result.globals = self.globals.values()
+                                     .map(|global| global.map_paths(&c_name))
+                                     .collect::<Vec<_>>();
 
         // Do one last pass to do renaming for all the items
         for item in &mut result.items {
@@ -424,6 +1589,19 @@ impl<'a> Library<'a> {
         for func in &mut result.functions {
             func.apply_renaming(self.config);
         }
+        for constant in &mut result.constants {
+            constant.apply_renaming(self.config);
+        }
+
+        // The shims of a module are written in a module of their own, which
+        // imports what the module does to name the same types
+        for shim in &self.shims {
+            if !shim.mod_path.is_empty() && !result.shim_imports.contains_key(&shim.mod_path) {
+                let imports = self.modules.use_paths(&self.bindings_crate_name, &shim.mod_path);
+                result.shim_imports.insert(shim.mod_path.clone(), imports);
+            }
+        }
+        result.shims = self.shims;
 
         Ok(result)
     }
@@ -434,16 +1612,25 @@ impl<'a> Library<'a> {
 pub struct BuiltBindings<'a> {
     config: &'a Config,
 
+    constants: Vec<Constant>,
     items: Vec<PathValue>,
+    globals: Vec<Static>,
     functions: Vec<Function>,
+    shims: Vec<Shim>,
+    /// The paths imported by the modules of the shims, from the crate root
+    shim_imports: BTreeMap<Vec<String>, Vec<String>>,
 }
 
 impl<'a> BuiltBindings<'a> {
     fn blank(config: &'a Config) -> BuiltBindings<'a> {
         BuiltBindings {
             config: config,
+            constants: Vec::new(),
             items: Vec::new(),
+            globals: Vec::new(),
             functions: Vec::new(),
+            shims: Vec::new(),
+            shim_imports: BTreeMap::new(),
         }
     }
 
@@ -451,6 +1638,93 @@ impl<'a> BuiltBindings<'a> {
         self.write(File::create(path).unwrap());
     }
 
+    /// Whether shims were generated for `impl` blocks, which have to be
+    /// written out with `write_shims` for their declarations to link.
+    pub fn has_shims(&self) -> bool {
+        !self.shims.is_empty()
+    }
+
+    pub fn write_shims_to_file(&self, path: &str) {
+        self.write_shims(File::create(path).unwrap());
+    }
+
+    /// Writes the Rust source of the shims, to be included in the crate.
+    pub fn write_shims<F: Write>(&self, mut file: F) {
+        if self.config.include_version {
+            write!(file, "// Generated with cbindgen:{}\n\n", config::VERSION).unwrap();
+        }
+
+        let mut modules = BTreeMap::new();
+        for shim in &self.shims {
+            modules.entry(&shim.mod_path).or_insert_with(Vec::new).push(shim);
+        }
+
+        // The shims of the crate root can see everything the root does
+        if let Some(shims) = modules.remove(&Vec::new()) {
+            for (i, shim) in shims.iter().enumerate() {
+                if i != 0 {
+                    writeln!(file).unwrap();
+                }
+                write!(file, "{}", shim.source).unwrap();
+            }
+            if !modules.is_empty() {
+                writeln!(file).unwrap();
+            }
+        }
+        if modules.is_empty() {
+            return;
+        }
+
+        // The shims of other modules are written in a copy of the module
+        // tree, as the private imports of a module can only be used in it
+        writeln!(file, "mod cbindgen_shims {{").unwrap();
+        let mut open: Vec<String> = Vec::new();
+        let mut needs_new_line = false;
+
+        for (mod_path, shims) in modules {
+            while !mod_path.starts_with(&open) {
+                open.pop();
+                writeln!(file, "{}}}", indent(open.len() + 1)).unwrap();
+                needs_new_line = true;
+            }
+            for segment in &mod_path[open.len()..] {
+                if needs_new_line {
+                    writeln!(file).unwrap();
+                }
+                writeln!(file, "{}mod {} {{", indent(open.len() + 1), segment).unwrap();
+                open.push(segment.clone());
+                needs_new_line = false;
+            }
+
+            let prefix = indent(open.len() + 1);
+            if needs_new_line {
+                writeln!(file).unwrap();
+            }
+            writeln!(file, "{}use crate::{}::*;", prefix, mod_path.join("::")).unwrap();
+            if let Some(imports) = self.shim_imports.get(mod_path) {
+                for import in imports {
+                    writeln!(file, "{}use {};", prefix, import).unwrap();
+                }
+            }
+            for shim in shims {
+                writeln!(file).unwrap();
+                for line in shim.source.lines() {
+                    if line.is_empty() {
+                        writeln!(file).unwrap();
+                    } else {
+                        writeln!(file, "{}{}", prefix, line).unwrap();
+                    }
+                }
+            }
+            needs_new_line = true;
+        }
+
+        for depth in (0..open.len()).rev() {
+            writeln!(file, "{}}}", indent(depth + 1)).unwrap();
+        }
+        writeln!(file, "}}").unwrap();
+    }
+
     pub fn write<F: Write>(&self, file: F) {
         let mut out = SourceWriter::new(file, self.config);
 
@@ -479,29 +1753,49 @@ impl<'a> BuiltBindings<'a> {
 
         out.new_line_if_not_start();
         out.write("#include <stdint.h>");
+        out.new_line();
+        out.write("#include <stddef.h>");
         if self.config.language == Language::C {
             out.new_line();
             out.write("#include <stdbool.h>");
         }
+        for include in &self.config.includes {
+            out.new_line();
+            out.write(&format!("#include <{}>", include));
+        }
         out.new_line();
 
-        if self.config.language == Language::Cxx {
+        // Templates can't have C linkage, so they are written before the
+        // `extern "C"` block along with the other items
+        let templates = self.config.generics_as_templates();
+
+        if self.config.language == Language::Cxx && !templates {
             out.new_line_if_not_start();
             out.write("extern \"C\" {");
             out.new_line();
         }
 
+        for constant in &self.constants {
+            out.new_line_if_not_start();
+            constant.cfg.write_before(self.config, &mut out);
+            constant.write(self.config, &mut out);
+            constant.cfg.write_after(self.config, &mut out);
+            out.new_line();
+        }
+
         for item in &self.items {
             out.new_line_if_not_start();
+            item.cfg().write_before(self.config, &mut out);
             match item {
                 &PathValue::Enum(ref x) => x.write(self.config, &mut out),
                 &PathValue::Struct(ref x) => x.write(self.config, &mut out),
+                &PathValue::Union(ref x) => x.write(self.config, &mut out),
                 &PathValue::OpaqueStruct(ref x) => x.write(self.config, &mut out),
                 &PathValue::Typedef(ref x) => x.write(self.config, &mut out),
-                &PathValue::Specialization(_) => {
-                    panic!("should not encounter a specialization in a built library")
-                }
+                &PathValue::Bitflags(ref x) => x.write(self.config, &mut out),
+                &PathValue::Specialization(ref x) => x.write(self.config, &mut out),
             }
+            item.cfg().write_after(self.config, &mut out);
             out.new_line();
         }
 
@@ -511,13 +1805,29 @@ impl<'a> BuiltBindings<'a> {
             out.new_line();
         }
 
+        if templates {
+            out.new_line_if_not_start();
+            out.write("extern \"C\" {");
+            out.new_line();
+        }
+
+        for global in &self.globals {
+            out.new_line_if_not_start();
+            global.cfg.write_before(self.config, &mut out);
+            global.write(self.config, &mut out);
+            global.cfg.write_after(self.config, &mut out);
+            out.new_line();
+        }
+
         for function in &self.functions {
             if function.extern_decl {
                 continue;
             }
 
             out.new_line_if_not_start();
+            function.cfg.write_before(self.config, &mut out);
             function.write(self.config, &mut out);
+            function.cfg.write_after(self.config, &mut out);
             out.new_line();
         }
 
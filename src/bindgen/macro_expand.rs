@@ -0,0 +1,637 @@
+use std::collections::HashMap;
+
+use syn;
+use syn::{BinOpToken, DelimToken, Delimited, Token, TokenTree};
+
+use bindgen::library::ConvertResult;
+use bindgen::utilities::{tokens_to_string, SynItemHelpers};
+
+// A small expander for `macro_rules!` macros that are invoked in item
+// position, so that FFI items stamped out by a local macro can be found
+// without expanding the crate with rustc. Rules are tried in order and
+// matched by backtracking over the invocation's token trees. Hygiene is
+// not modelled, and the only fragment kinds supported are the ones that
+// can be matched without a full parser.
+
+/// Macros can expand to invocations of other macros, which are expanded
+/// as well, up to this depth.
+const MAX_EXPANSION_DEPTH: usize = 64;
+
+/// The `macro_rules!` macros defined in a crate.
+pub struct MacroTable {
+    macros: HashMap<String, Vec<Rule>>,
+}
+
+struct Rule {
+    matcher: Vec<Matcher>,
+    transcriber: Vec<TokenTree>,
+}
+
+enum Matcher {
+    Token(TokenTree),
+    Delimited(DelimToken, Vec<Matcher>),
+    Fragment(String, Fragment),
+    Repeat(Vec<Matcher>, Option<Token>, RepeatOp),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Fragment {
+    Ident,
+    Lifetime,
+    Literal,
+    Tt,
+    Block,
+    Vis,
+    Ty,
+    Path,
+    Expr,
+    Meta,
+    Item,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RepeatOp {
+    ZeroOrMore,
+    OneOrMore,
+    ZeroOrOne,
+}
+
+#[derive(Clone)]
+enum Binding {
+    Fragment(Fragment, Vec<TokenTree>),
+    Repeat(Vec<Binding>),
+}
+
+type Bindings = HashMap<String, Binding>;
+
+impl MacroTable {
+    pub fn new() -> MacroTable {
+        MacroTable {
+            macros: HashMap::new(),
+        }
+    }
+
+    /// Records the `macro_rules!` definitions in a module, and in the
+    /// inline `#[macro_use]` modules in it.
+    pub fn collect(&mut self, items: &[syn::Item]) {
+        for item in items {
+            match item.node {
+                syn::ItemKind::Mac(ref mac) if is_macro_rules(mac) => {
+                    self.define(item, mac);
+                }
+                syn::ItemKind::Mod(Some(ref inline_items)) if item.is_macro_use() => {
+                    self.collect(inline_items);
+                }
+                _ => { }
+            }
+        }
+    }
+
+    /// Replaces the invocations of known macros in a list of items with
+    /// the items they expand to.
+    pub fn expand(&mut self, items: &[syn::Item]) -> Vec<syn::Item> {
+        self.expand_items(items, 0)
+    }
+
+    fn define(&mut self, item: &syn::Item, mac: &syn::Mac) {
+        let name = item.ident.to_string();
+
+        match parse_rules(mac) {
+            Ok(rules) => {
+                self.macros.insert(name, rules);
+            }
+            Err(msg) => {
+                warn!("skipping macro_rules! {} - ({})", name, msg);
+            }
+        }
+    }
+
+    fn expand_items(&mut self, items: &[syn::Item], depth: usize) -> Vec<syn::Item> {
+        let mut result = Vec::new();
+
+        for item in items {
+            if let syn::ItemKind::Mac(ref mac) = item.node {
+                if is_macro_rules(mac) {
+                    self.define(item, mac);
+                } else if let Some(name) = self.invoked_macro(mac) {
+                    if depth >= MAX_EXPANSION_DEPTH {
+                        warn!("skipping invocation of {}! - (recursion limit reached)", name);
+                        continue;
+                    }
+
+                    match self.expand_invocation(&name, mac) {
+                        Ok(mut expanded) => {
+                            // A cfg on the invocation applies to everything
+                            // that it expands to
+                            let cfgs = item.attrs.iter()
+                                                 .filter(|x| is_cfg_attr(x))
+                                                 .cloned()
+                                                 .collect::<Vec<_>>();
+                            for expanded_item in &mut expanded {
+                                let mut attrs = cfgs.clone();
+                                attrs.extend(expanded_item.attrs.drain(..));
+                                expanded_item.attrs = attrs;
+                            }
+
+                            result.extend(self.expand_items(&expanded, depth + 1));
+                        }
+                        Err(msg) => {
+                            warn!("skipping invocation of {}! - ({})", name, msg);
+                        }
+                    }
+                    continue;
+                }
+            }
+
+            result.push(item.clone());
+        }
+
+        result
+    }
+
+    /// The name of the macro invoked, if it is a known `macro_rules!` macro.
+    fn invoked_macro(&self, mac: &syn::Mac) -> Option<String> {
+        if mac.path.global || mac.path.segments.len() != 1 {
+            return None;
+        }
+
+        let name = mac.path.segments[0].ident.to_string();
+        if self.macros.contains_key(&name) {
+            Some(name)
+        } else {
+            None
+        }
+    }
+
+    fn expand_invocation(&self, name: &str, mac: &syn::Mac) -> ConvertResult<Vec<syn::Item>> {
+        let input = match mac.tts.first() {
+            Some(&TokenTree::Delimited(ref body)) if mac.tts.len() == 1 => &body.tts,
+            _ => return Err(format!("malformed invocation")),
+        };
+
+        for rule in &self.macros[name] {
+            if let Some(bindings) = match_seq(&rule.matcher, input) {
+                let output = transcribe(&rule.transcriber, &bindings)?;
+                return syn::parse_items(&tokens_to_string(&output))
+                           .map_err(|msg| format!("expansion isn't a list of items: {}", msg));
+            }
+        }
+
+        Err(format!("no rules matched the invocation"))
+    }
+}
+
+fn is_macro_rules(mac: &syn::Mac) -> bool {
+    !mac.path.global && mac.path.segments.len() == 1 &&
+    mac.path.segments[0].ident == "macro_rules"
+}
+
+fn is_cfg_attr(attr: &syn::Attribute) -> bool {
+    match attr.value {
+        syn::MetaItem::List(ref name, _) => name == "cfg",
+        _ => false,
+    }
+}
+
+/// Parses the `(matcher) => { transcriber };` rules of a `macro_rules!`.
+fn parse_rules(mac: &syn::Mac) -> ConvertResult<Vec<Rule>> {
+    let body = match mac.tts.first() {
+        Some(&TokenTree::Delimited(ref body)) if mac.tts.len() == 1 => &body.tts,
+        _ => return Err(format!("malformed definition")),
+    };
+
+    let mut rules = Vec::new();
+    let mut i = 0;
+
+    while i < body.len() {
+        match (&body[i], body.get(i + 1), body.get(i + 2)) {
+            (&TokenTree::Delimited(ref matcher),
+             Some(&TokenTree::Token(Token::FatArrow)),
+             Some(&TokenTree::Delimited(ref transcriber))) => {
+                rules.push(Rule {
+                    matcher: parse_matcher(&matcher.tts)?,
+                    transcriber: transcriber.tts.clone(),
+                });
+                i += 3;
+            }
+            _ => return Err(format!("expected a `(..) => {{..}}` rule")),
+        }
+
+        if body.get(i) == Some(&TokenTree::Token(Token::Semi)) {
+            i += 1;
+        }
+    }
+
+    Ok(rules)
+}
+
+fn parse_matcher(tts: &[TokenTree]) -> ConvertResult<Vec<Matcher>> {
+    let mut result = Vec::new();
+    let mut i = 0;
+
+    while i < tts.len() {
+        match (&tts[i], tts.get(i + 1)) {
+            (&TokenTree::Token(Token::Dollar), Some(&TokenTree::Token(Token::Ident(ref name)))) => {
+                let kind = match (tts.get(i + 2), tts.get(i + 3)) {
+                    (Some(&TokenTree::Token(Token::Colon)),
+                     Some(&TokenTree::Token(Token::Ident(ref kind)))) => parse_fragment(kind.as_ref())?,
+                    _ => return Err(format!("expected a fragment kind for ${}", name)),
+                };
+                result.push(Matcher::Fragment(name.to_string(), kind));
+                i += 4;
+            }
+            (&TokenTree::Token(Token::Dollar), Some(&TokenTree::Delimited(ref group)))
+                if group.delim == DelimToken::Paren => {
+                let (separator, op, len) = parse_repeat_op(&tts[i + 2..])?;
+                result.push(Matcher::Repeat(parse_matcher(&group.tts)?, separator, op));
+                i += 2 + len;
+            }
+            (&TokenTree::Delimited(ref group), _) => {
+                result.push(Matcher::Delimited(group.delim, parse_matcher(&group.tts)?));
+                i += 1;
+            }
+            (token, _) => {
+                result.push(Matcher::Token(token.clone()));
+                i += 1;
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+fn parse_fragment(kind: &str) -> ConvertResult<Fragment> {
+    match kind {
+        "ident" => Ok(Fragment::Ident),
+        "lifetime" => Ok(Fragment::Lifetime),
+        "literal" => Ok(Fragment::Literal),
+        "tt" => Ok(Fragment::Tt),
+        "block" => Ok(Fragment::Block),
+        "vis" => Ok(Fragment::Vis),
+        "ty" => Ok(Fragment::Ty),
+        "path" => Ok(Fragment::Path),
+        "expr" => Ok(Fragment::Expr),
+        "meta" => Ok(Fragment::Meta),
+        "item" => Ok(Fragment::Item),
+        _ => Err(format!("unsupported fragment kind `{}`", kind)),
+    }
+}
+
+/// Parses the optional separator and the operator after a `$(..)`,
+/// returning them and the number of tokens they take.
+fn parse_repeat_op(tts: &[TokenTree]) -> ConvertResult<(Option<Token>, RepeatOp, usize)> {
+    let op = |tt: Option<&TokenTree>| {
+        match tt {
+            Some(&TokenTree::Token(Token::BinOp(BinOpToken::Star))) => Some(RepeatOp::ZeroOrMore),
+            Some(&TokenTree::Token(Token::BinOp(BinOpToken::Plus))) => Some(RepeatOp::OneOrMore),
+            Some(&TokenTree::Token(Token::Question)) => Some(RepeatOp::ZeroOrOne),
+            _ => None,
+        }
+    };
+
+    if let Some(op) = op(tts.get(0)) {
+        return Ok((None, op, 1));
+    }
+    match (tts.get(0), op(tts.get(1))) {
+        (Some(&TokenTree::Token(ref separator)), Some(op)) => Ok((Some(separator.clone()), op, 2)),
+        _ => Err(format!("expected a repetition operator")),
+    }
+}
+
+/// Matches all of `tts` against a sequence of matchers.
+fn match_seq(matchers: &[Matcher], tts: &[TokenTree]) -> Option<Bindings> {
+    let (first, rest) = match matchers.split_first() {
+        Some(x) => x,
+        None if tts.is_empty() => return Some(HashMap::new()),
+        None => return None,
+    };
+
+    match first {
+        &Matcher::Token(ref token) => {
+            if tts.first() != Some(token) {
+                return None;
+            }
+            match_seq(rest, &tts[1..])
+        }
+        &Matcher::Delimited(delim, ref inner) => {
+            match tts.first() {
+                Some(&TokenTree::Delimited(ref group)) if group.delim == delim => {
+                    let mut bindings = match_seq(inner, &group.tts)?;
+                    bindings.extend(match_seq(rest, &tts[1..])?);
+                    Some(bindings)
+                }
+                _ => None,
+            }
+        }
+        &Matcher::Fragment(ref name, kind) => {
+            let len = match_fragment(kind, tts)?;
+            let mut bindings = match_seq(rest, &tts[len..])?;
+            bindings.insert(name.clone(), Binding::Fragment(kind, tts[..len].to_vec()));
+            Some(bindings)
+        }
+        &Matcher::Repeat(ref body, ref separator, op) => {
+            // Match as many repetitions as possible, then back off until
+            // the rest of the matchers match what is left
+            let mut iterations: Vec<(usize, Bindings)> = Vec::new();
+            let mut pos = 0;
+
+            while op != RepeatOp::ZeroOrOne || iterations.is_empty() {
+                let mut start = pos;
+                if let (&Some(ref separator), false) = (separator, iterations.is_empty()) {
+                    if tts.get(pos) != Some(&TokenTree::Token(separator.clone())) {
+                        break;
+                    }
+                    start += 1;
+                }
+
+                // Each repetition takes at least one token, so that this ends.
+                // The ends up to the likely one are tried first, so that
+                // each repetition doesn't parse the rest of the input again
+                let limit = repeat_len(body, separator, rest, &tts[start..]).map(|x| start + x)
+                                                                            .unwrap_or(tts.len());
+                let found = (start + 1..limit + 1).rev()
+                                                  .chain((limit + 1..tts.len() + 1).rev())
+                                                  .filter_map(|end| {
+                                                      match_seq(body, &tts[start..end]).map(|x| (end, x))
+                                                  })
+                                                  .next();
+                match found {
+                    Some((end, bindings)) => {
+                        iterations.push((end, bindings));
+                        pos = end;
+                    }
+                    None => break,
+                }
+            }
+
+            let min = if op == RepeatOp::OneOrMore { 1 } else { 0 };
+            while iterations.len() >= min {
+                let end = iterations.last().map(|x| x.0).unwrap_or(0);
+
+                if let Some(mut bindings) = match_seq(rest, &tts[end..]) {
+                    for name in fragment_names(body) {
+                        let repeated = iterations.iter()
+                                                 .filter_map(|x| x.1.get(&name).cloned())
+                                                 .collect();
+                        bindings.insert(name, Binding::Repeat(repeated));
+                    }
+                    return Some(bindings);
+                }
+
+                if iterations.pop().is_none() {
+                    break;
+                }
+            }
+            None
+        }
+    }
+}
+
+/// The number of tokens a repetition likely takes: up to its separator or
+/// the token after the repetition, or through the token its body ends with.
+fn repeat_len(body: &[Matcher],
+              separator: &Option<Token>,
+              rest: &[Matcher],
+              tts: &[TokenTree])
+              -> Option<usize> {
+    let follow = match rest.first() {
+        Some(&Matcher::Token(ref token)) => Some(token),
+        _ => None,
+    };
+    let last = match body.last() {
+        Some(&Matcher::Token(ref token)) => Some(token),
+        _ => None,
+    };
+
+    (1..tts.len() + 1).find(|&len| {
+        let next = tts.get(len);
+        let at_separator = match (next, separator) {
+            (Some(&TokenTree::Token(ref x)), &Some(ref separator)) => x == separator,
+            _ => false,
+        };
+        at_separator || (next.is_some() && next == follow) ||
+        (last.is_some() && Some(&tts[len - 1]) == last)
+    })
+}
+
+/// The number of tokens at the start of `tts` that a fragment matches.
+fn match_fragment(kind: Fragment, tts: &[TokenTree]) -> Option<usize> {
+    match kind {
+        Fragment::Ident => {
+            match tts.first() {
+                Some(&TokenTree::Token(Token::Ident(_))) => Some(1),
+                _ => None,
+            }
+        }
+        Fragment::Lifetime => {
+            match tts.first() {
+                Some(&TokenTree::Token(Token::Lifetime(_))) => Some(1),
+                _ => None,
+            }
+        }
+        Fragment::Literal => {
+            match (tts.get(0), tts.get(1)) {
+                (Some(&TokenTree::Token(Token::Literal(_))), _) => Some(1),
+                (Some(&TokenTree::Token(Token::BinOp(BinOpToken::Minus))),
+                 Some(&TokenTree::Token(Token::Literal(_)))) => Some(2),
+                _ => None,
+            }
+        }
+        Fragment::Tt => {
+            if tts.is_empty() { None } else { Some(1) }
+        }
+        Fragment::Block => {
+            match tts.first() {
+                Some(&TokenTree::Delimited(ref x)) if x.delim == DelimToken::Brace => Some(1),
+                _ => None,
+            }
+        }
+        Fragment::Vis => {
+            match (tts.get(0), tts.get(1)) {
+                (Some(&TokenTree::Token(Token::Ident(ref x))), Some(&TokenTree::Delimited(ref y)))
+                    if x == "pub" && y.delim == DelimToken::Paren => Some(2),
+                (Some(&TokenTree::Token(Token::Ident(ref x))), _) if x == "pub" => Some(1),
+                // The visibility can be empty
+                _ => Some(0),
+            }
+        }
+        Fragment::Ty | Fragment::Path => {
+            let len = type_len(tts);
+            let parsed = if kind == Fragment::Ty {
+                syn::parse_type(&tokens_to_string(&tts[..len])).is_ok()
+            } else {
+                syn::parse_path(&tokens_to_string(&tts[..len])).is_ok()
+            };
+            if len > 0 && parsed { Some(len) } else { None }
+        }
+        Fragment::Expr | Fragment::Meta => {
+            // Expressions can only be followed by `=>`, `,` or `;`
+            let len = tts.iter()
+                         .position(|x| match x {
+                             &TokenTree::Token(Token::FatArrow) |
+                             &TokenTree::Token(Token::Comma) |
+                             &TokenTree::Token(Token::Semi) => true,
+                             _ => false,
+                         })
+                         .unwrap_or(tts.len());
+            let parsed = if kind == Fragment::Expr {
+                syn::parse_expr(&tokens_to_string(&tts[..len])).is_ok()
+            } else {
+                syn::parse_outer_attr(&format!("#[{}]", tokens_to_string(&tts[..len]))).is_ok()
+            };
+            if len > 0 && parsed { Some(len) } else { None }
+        }
+        Fragment::Item => {
+            // An item ends with a `;` or a braced body
+            (0..tts.len()).filter(|&i| match &tts[i] {
+                              &TokenTree::Token(Token::Semi) => true,
+                              &TokenTree::Delimited(ref x) => x.delim == DelimToken::Brace,
+                              _ => false,
+                          })
+                          .map(|i| i + 1)
+                          .find(|&len| syn::parse_item(&tokens_to_string(&tts[..len])).is_ok())
+        }
+    }
+}
+
+/// The number of tokens at the start of `tts` that could be a type or a
+/// path, stopping at the tokens that are allowed to follow them.
+fn type_len(tts: &[TokenTree]) -> usize {
+    let mut depth = 0;
+
+    for (i, tt) in tts.iter().enumerate() {
+        match tt {
+            &TokenTree::Token(Token::Lt) => depth += 1,
+            &TokenTree::Token(Token::BinOp(BinOpToken::Shl)) => depth += 2,
+            &TokenTree::Token(Token::Gt) if depth > 0 => depth -= 1,
+            &TokenTree::Token(Token::BinOp(BinOpToken::Shr)) if depth > 1 => depth -= 2,
+            &TokenTree::Token(Token::Gt) |
+            &TokenTree::Token(Token::BinOp(BinOpToken::Shr)) => return i,
+            &TokenTree::Token(Token::Comma) |
+            &TokenTree::Token(Token::Semi) |
+            &TokenTree::Token(Token::Colon) |
+            &TokenTree::Token(Token::Eq) |
+            &TokenTree::Token(Token::FatArrow) |
+            &TokenTree::Token(Token::BinOp(BinOpToken::Or)) if depth == 0 => return i,
+            &TokenTree::Token(Token::Ident(ref x)) if depth == 0 && (x == "as" || x == "where") => return i,
+            &TokenTree::Delimited(ref x) if depth == 0 && i > 0 && x.delim == DelimToken::Brace => return i,
+            _ => { }
+        }
+    }
+
+    tts.len()
+}
+
+/// The names of the fragments bound by a sequence of matchers.
+fn fragment_names(matchers: &[Matcher]) -> Vec<String> {
+    let mut names = Vec::new();
+
+    for matcher in matchers {
+        match matcher {
+            &Matcher::Fragment(ref name, _) => names.push(name.clone()),
+            &Matcher::Delimited(_, ref inner) |
+            &Matcher::Repeat(ref inner, ..) => names.extend(fragment_names(inner)),
+            &Matcher::Token(_) => { }
+        }
+    }
+
+    names
+}
+
+/// The names of the fragments used in a transcriber.
+fn transcribed_names(tts: &[TokenTree], names: &mut Vec<String>) {
+    for (i, tt) in tts.iter().enumerate() {
+        match (tt, tts.get(i + 1)) {
+            (&TokenTree::Token(Token::Dollar), Some(&TokenTree::Token(Token::Ident(ref name)))) => {
+                names.push(name.to_string());
+            }
+            (&TokenTree::Delimited(ref group), _) => transcribed_names(&group.tts, names),
+            _ => { }
+        }
+    }
+}
+
+fn transcribe(tts: &[TokenTree], bindings: &Bindings) -> ConvertResult<Vec<TokenTree>> {
+    let mut result = Vec::new();
+    let mut i = 0;
+
+    while i < tts.len() {
+        match (&tts[i], tts.get(i + 1)) {
+            (&TokenTree::Token(Token::Dollar), Some(&TokenTree::Token(Token::Ident(ref name)))) => {
+                i += 2;
+
+                // `$crate::path` refers to the root of the crate
+                if name == "crate" {
+                    continue;
+                }
+
+                match bindings.get(name.as_ref()) {
+                    Some(&Binding::Fragment(kind, ref tokens)) => {
+                        // Expressions are substituted as a single operand
+                        if kind == Fragment::Expr && tokens.len() > 1 {
+                            result.push(TokenTree::Delimited(Delimited {
+                                delim: DelimToken::Paren,
+                                tts: tokens.clone(),
+                            }));
+                        } else {
+                            result.extend(tokens.iter().cloned());
+                        }
+                    }
+                    Some(&Binding::Repeat(_)) => {
+                        return Err(format!("${} is still repeating at this depth", name));
+                    }
+                    None => return Err(format!("unknown macro variable ${}", name)),
+                }
+            }
+            (&TokenTree::Token(Token::Dollar), Some(&TokenTree::Delimited(ref group)))
+                if group.delim == DelimToken::Paren => {
+                let (separator, _, len) = parse_repeat_op(&tts[i + 2..])?;
+                i += 2 + len;
+
+                let mut names = Vec::new();
+                transcribed_names(&group.tts, &mut names);
+
+                // Repeat once for each value of the repeated fragments used
+                let mut count = None;
+                for name in &names {
+                    if let Some(&Binding::Repeat(ref values)) = bindings.get(name) {
+                        if count.is_some() && count != Some(values.len()) {
+                            return Err(format!("${} repeats a different number of times", name));
+                        }
+                        count = Some(values.len());
+                    }
+                }
+                let count = count.ok_or_else(|| format!("a repetition doesn't use any repeated fragment"))?;
+
+                for n in 0..count {
+                    if n != 0 {
+                        if let Some(ref separator) = separator {
+                            result.push(TokenTree::Token(separator.clone()));
+                        }
+                    }
+
+                    let mut iteration = bindings.clone();
+                    for name in &names {
+                        if let Some(&Binding::Repeat(ref values)) = bindings.get(name) {
+                            iteration.insert(name.clone(), values[n].clone());
+                        }
+                    }
+                    result.extend(transcribe(&group.tts, &iteration)?);
+                }
+            }
+            (&TokenTree::Delimited(ref group), _) => {
+                result.push(TokenTree::Delimited(Delimited {
+                    delim: group.delim,
+                    tts: transcribe(&group.tts, bindings)?,
+                }));
+                i += 1;
+            }
+            (tt, _) => {
+                result.push(tt.clone());
+                i += 1;
+            }
+        }
+    }
+
+    Ok(result)
+}
@@ -27,15 +27,21 @@ macro_rules! deserialize_enum_str {
     }
 }
 
+mod bitflags;
 mod cargo_expand;
 mod cargo_metadata;
 mod cdecl;
+mod cfg;
 mod config;
+mod const_eval;
+mod documentation;
 mod annotation;
 mod items;
 mod library;
+mod macro_expand;
 mod rename;
 mod rust_lib;
+mod shims;
 mod utilities;
 mod writer;
 
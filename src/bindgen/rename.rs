@@ -6,6 +6,7 @@ pub enum IdentifierType {
     StructMember,
     EnumVariant,
     FunctionArg,
+    Constant,
 }
 impl IdentifierType {
     fn to_str(&self) -> &'static str {
@@ -13,6 +14,7 @@ impl IdentifierType {
             IdentifierType::StructMember => "m",
             IdentifierType::EnumVariant => "",
             IdentifierType::FunctionArg => "a",
+            IdentifierType::Constant => "k",
         }
     }
 }
@@ -5,6 +5,9 @@ use std::path::{Path, PathBuf};
 
 use bindgen::cargo_expand;
 use bindgen::cargo_metadata;
+use bindgen::config::{ExpandConfig, ExpandStrategy};
+use bindgen::macro_expand::MacroTable;
+use bindgen::utilities::SynItemHelpers;
 use syn;
 
 const STD_CRATES: &[&'static str] = &["std",
@@ -16,10 +19,11 @@ const STD_CRATES: &[&'static str] = &["std",
 
 type ParseResult = Result<(), String>;
 
-/// Parses a single rust source file, not following `mod` or `extern crate`.
+/// Parses a single rust source file, following inline `mod`s but not
+/// `mod` declarations in other files or `extern crate`.
 pub fn parse_src<F>(src_file: &Path,
                     items_callback: &mut F) -> ParseResult
-    where F: FnMut(&str, &Vec<syn::Item>)
+    where F: FnMut(&str, &[String], &Vec<syn::Item>)
 {
     let src_parsed = {
         let mut s = String::new();
@@ -28,11 +32,31 @@ pub fn parse_src<F>(src_file: &Path,
         syn::parse_crate(&s).map_err(|msg| format!("parsing:\n{}", msg))?
     };
 
-    items_callback("", &src_parsed.items);
+    process_src_mod(&mut Vec::new(), &src_parsed.items, &mut MacroTable::new(), items_callback);
 
     Ok(())
 }
 
+fn process_src_mod<F>(mod_path: &mut Vec<String>,
+                      items: &Vec<syn::Item>,
+                      macros: &mut MacroTable,
+                      items_callback: &mut F)
+    where F: FnMut(&str, &[String], &Vec<syn::Item>)
+{
+    macros.collect(items);
+    let items = macros.expand(items);
+
+    items_callback("", mod_path, &items);
+
+    for item in &items {
+        if let syn::ItemKind::Mod(Some(ref inline_items)) = item.node {
+            mod_path.push(item.ident.to_string());
+            process_src_mod(mod_path, inline_items, macros, items_callback);
+            mod_path.pop();
+        }
+    }
+}
+
 /// Recursively parses a rust library starting at the root crate's directory.
 ///
 /// Inside a crate, `mod` and `extern crate` declarations are followed
@@ -40,9 +64,9 @@ pub fn parse_src<F>(src_file: &Path,
 /// command to find the location of dependencies.
 pub fn parse_lib<F>(crate_path: &Path,
                     binding_crate_name: &str,
-                    expand: &[String],
+                    expand: &ExpandConfig,
                     items_callback: &mut F) -> ParseResult
-    where F: FnMut(&str, &Vec<syn::Item>)
+    where F: FnMut(&str, &[String], &Vec<syn::Item>)
 {
     let manifest_path = crate_path.join("Cargo.toml");
 
@@ -56,9 +80,10 @@ pub fn parse_lib<F>(crate_path: &Path,
     let mut context = ParseLibContext {
         manifest_path: manifest_path,
         metadata: metadata,
-        expand: expand.to_owned(),
+        expand: expand.clone(),
         cache_src: HashMap::new(),
         cache_expanded_crate: HashMap::new(),
+        macros: HashMap::new(),
         items_callback: items_callback,
     };
 
@@ -66,19 +91,22 @@ pub fn parse_lib<F>(crate_path: &Path,
 }
 
 struct ParseLibContext<F>
-  where F: FnMut(&str, &Vec<syn::Item>)
+  where F: FnMut(&str, &[String], &Vec<syn::Item>)
 {
     manifest_path: PathBuf,
     metadata: cargo_metadata::Metadata,
-    expand: Vec<String>,
+    expand: ExpandConfig,
     cache_src: HashMap<PathBuf, Vec<syn::Item>>,
     cache_expanded_crate: HashMap<String, Vec<syn::Item>>,
+    /// The `macro_rules!` macros defined in each crate, which are expanded
+    /// by cbindgen itself
+    macros: HashMap<String, MacroTable>,
 
     items_callback: F,
 }
 
 impl<F> ParseLibContext<F>
-  where F: FnMut(&str, &Vec<syn::Item>)
+  where F: FnMut(&str, &[String], &Vec<syn::Item>)
 {
   fn find_crate_src(&self, package_name: &str) -> Option<PathBuf> {
       let kind_lib = String::from("lib");
@@ -97,21 +125,21 @@ impl<F> ParseLibContext<F>
 }
 
 fn parse_crate<F>(crate_name: &str, context: &mut ParseLibContext<F>) -> ParseResult
-    where F: FnMut(&str, &Vec<syn::Item>)
+    where F: FnMut(&str, &[String], &Vec<syn::Item>)
 {
     if STD_CRATES.contains(&crate_name) {
         return Ok(());
     }
 
-    if context.expand.contains(&crate_name.to_owned()) {
-        return parse_expand_crate(crate_name, context);
+    if let Some(strategy) = context.expand.strategy(crate_name) {
+        return parse_expand_crate(crate_name, strategy, context);
     }
 
     let crate_src = context.find_crate_src(crate_name);
 
     match crate_src {
         Some(crate_src) => {
-            parse_mod(crate_name, crate_src.as_path(), context)
+            parse_mod(crate_name, &mut Vec::new(), crate_src.as_path(), context)
         },
         None => {
             // This should be an error, but is common enough to just elicit a warning
@@ -121,14 +149,16 @@ fn parse_crate<F>(crate_name: &str, context: &mut ParseLibContext<F>) -> ParseRe
     }
 }
 
-fn parse_expand_crate<F>(crate_name: &str, context: &mut ParseLibContext<F>) -> ParseResult
-    where F: FnMut(&str, &Vec<syn::Item>)
+fn parse_expand_crate<F>(crate_name: &str,
+                         strategy: ExpandStrategy,
+                         context: &mut ParseLibContext<F>) -> ParseResult
+    where F: FnMut(&str, &[String], &Vec<syn::Item>)
 {
     let mod_parsed = {
         let owned_crate_name = crate_name.to_owned();
 
         if !context.cache_expanded_crate.contains_key(&owned_crate_name) {
-            let s = cargo_expand::expand(&context.manifest_path, crate_name)?;
+            let s = cargo_expand::expand(&context.manifest_path, crate_name, strategy)?;
             let i = syn::parse_crate(&s).map_err(|msg| format!("parsing crate `{}`:\n{}", crate_name, msg))?;
             context.cache_expanded_crate.insert(owned_crate_name.clone(), i.items);
         }
@@ -136,21 +166,24 @@ fn parse_expand_crate<F>(crate_name: &str, context: &mut ParseLibContext<F>) ->
         context.cache_expanded_crate.get(&owned_crate_name).unwrap().clone()
     };
 
-    process_expanded_mod(crate_name, &mod_parsed, context)
+    process_expanded_mod(crate_name, &mut Vec::new(), &mod_parsed, context)
 }
 
 fn process_expanded_mod<F>(crate_name: &str,
+                           mod_path: &mut Vec<String>,
                            items: &Vec<syn::Item>,
                            context: &mut ParseLibContext<F>) -> ParseResult
-    where F: FnMut(&str, &Vec<syn::Item>)
+    where F: FnMut(&str, &[String], &Vec<syn::Item>)
 {
-    (context.items_callback)(crate_name, items);
+    (context.items_callback)(crate_name, mod_path, items);
 
     for item in items {
         match item.node {
             syn::ItemKind::Mod(ref inline_items) => {
                 if let &Some(ref inline_items) = inline_items {
-                    process_expanded_mod(crate_name, inline_items, context)?;
+                    mod_path.push(item.ident.to_string());
+                    process_expanded_mod(crate_name, mod_path, inline_items, context)?;
+                    mod_path.pop();
                     continue;
                 }
 
@@ -166,69 +199,124 @@ fn process_expanded_mod<F>(crate_name: &str,
     Ok(())
 }
 
-fn parse_mod<F>(crate_name: &str,
-                mod_path: &Path,
-                context: &mut ParseLibContext<F>) -> ParseResult
-    where F: FnMut(&str, &Vec<syn::Item>)
+fn load_mod<F>(crate_name: &str,
+               mod_file: &Path,
+               context: &mut ParseLibContext<F>) -> Result<Vec<syn::Item>, String>
+    where F: FnMut(&str, &[String], &Vec<syn::Item>)
 {
-    let mod_parsed = {
-        let owned_mod_path = mod_path.to_path_buf();
+    let owned_mod_path = mod_file.to_path_buf();
 
-        if !context.cache_src.contains_key(&owned_mod_path) {
-            let mut s = String::new();
-            let mut f = File::open(mod_path).map_err(|_| format!("parsing crate `{}`: cannot open file `{:?}`", crate_name, mod_path))?;
-            f.read_to_string(&mut s).map_err(|_| format!("parsing crate `{}`: cannot open file `{:?}`", crate_name, mod_path))?;
-            let i = syn::parse_crate(&s).map_err(|msg| format!("parsing crate `{}`:\n{}", crate_name, msg))?;
-            context.cache_src.insert(owned_mod_path.clone(), i.items);
+    if !context.cache_src.contains_key(&owned_mod_path) {
+        let mut s = String::new();
+        let mut f = File::open(mod_file).map_err(|_| format!("parsing crate `{}`: cannot open file `{:?}`", crate_name, mod_file))?;
+        f.read_to_string(&mut s).map_err(|_| format!("parsing crate `{}`: cannot open file `{:?}`", crate_name, mod_file))?;
+        let i = syn::parse_crate(&s).map_err(|msg| format!("parsing crate `{}`:\n{}", crate_name, msg))?;
+        context.cache_src.insert(owned_mod_path.clone(), i.items);
+    }
+
+    Ok(context.cache_src.get(&owned_mod_path).unwrap().clone())
+}
+
+/// Finds the file of a `mod` declared in a file in `mod_dir`.
+fn find_mod_file(mod_dir: &Path, mod_name: &str) -> Option<PathBuf> {
+    let next_mod_path1 = mod_dir.join(String::from(mod_name) + ".rs");
+    let next_mod_path2 = mod_dir.join(mod_name).join("mod.rs");
+
+    if next_mod_path1.exists() {
+        Some(next_mod_path1)
+    } else if next_mod_path2.exists() {
+        Some(next_mod_path2)
+    } else {
+        None
+    }
+}
+
+/// Records the `macro_rules!` macros defined in a module, including the
+/// ones in `#[macro_use]` modules, which can be used after their `mod`.
+fn collect_macros<F>(crate_name: &str,
+                     mod_dir: &Path,
+                     items: &Vec<syn::Item>,
+                     context: &mut ParseLibContext<F>)
+    where F: FnMut(&str, &[String], &Vec<syn::Item>)
+{
+    context.macros.entry(crate_name.to_owned())
+                  .or_insert_with(MacroTable::new)
+                  .collect(items);
+
+    for item in items {
+        if let syn::ItemKind::Mod(None) = item.node {
+            if !item.is_macro_use() {
+                continue;
+            }
+
+            let mod_file = match find_mod_file(mod_dir, &item.ident.to_string()) {
+                Some(mod_file) => mod_file,
+                None => continue,
+            };
+            match load_mod(crate_name, &mod_file, context) {
+                Ok(mod_items) => collect_macros(crate_name, mod_file.parent().unwrap(), &mod_items, context),
+                Err(msg) => warn!("{}", msg),
+            }
         }
+    }
+}
 
-        context.cache_src.get(&owned_mod_path).unwrap().clone()
-    };
+fn parse_mod<F>(crate_name: &str,
+                mod_path: &mut Vec<String>,
+                mod_file: &Path,
+                context: &mut ParseLibContext<F>) -> ParseResult
+    where F: FnMut(&str, &[String], &Vec<syn::Item>)
+{
+    let mod_parsed = load_mod(crate_name, mod_file, context)?;
 
-    let mod_dir = mod_path.parent().unwrap();
+    let mod_dir = mod_file.parent().unwrap();
 
     process_mod(crate_name,
+                mod_path,
                 mod_dir,
                 &mod_parsed,
                 context)
 }
 
 fn process_mod<F>(crate_name: &str,
+                  mod_path: &mut Vec<String>,
                   mod_dir: &Path,
                   items: &Vec<syn::Item>,
                   context: &mut ParseLibContext<F>) -> ParseResult
-    where F: FnMut(&str, &Vec<syn::Item>)
+    where F: FnMut(&str, &[String], &Vec<syn::Item>)
 {
-    (context.items_callback)(crate_name, items);
+    collect_macros(crate_name, mod_dir, items, context);
+    let items = context.macros.get_mut(crate_name).unwrap().expand(items);
 
-    for item in items {
+    (context.items_callback)(crate_name, mod_path, &items);
+
+    for item in &items {
         match item.node {
             syn::ItemKind::Mod(ref inline_items) => {
                 let next_mod_name = item.ident.to_string();
 
                 if let &Some(ref inline_items) = inline_items {
+                    mod_path.push(next_mod_name);
                     process_mod(crate_name,
+                                mod_path,
                                 mod_dir,
                                 inline_items,
                                 context)?;
+                    mod_path.pop();
                     continue;
                 }
 
-                let next_mod_path1 = mod_dir.join(next_mod_name.clone() + ".rs");
-                let next_mod_path2 = mod_dir.join(next_mod_name.clone()).join("mod.rs");
-
-                if next_mod_path1.exists() {
-                    parse_mod(crate_name,
-                              next_mod_path1.as_path(),
-                              context)?;
-                } else if next_mod_path2.exists() {
+                mod_path.push(next_mod_name.clone());
+                if let Some(next_mod_path) = find_mod_file(mod_dir, &next_mod_name) {
                     parse_mod(crate_name,
-                              next_mod_path2.as_path(),
+                              mod_path,
+                              next_mod_path.as_path(),
                               context)?;
                 } else {
                     // This should be an error, but is common enough to just elicit a warning
                     warn!("parsing crate `{}`: can't find mod {}`", crate_name, next_mod_name);
                 }
+                mod_path.pop();
             }
             syn::ItemKind::ExternCrate(_) => {
                 parse_crate(&item.ident.to_string(), context)?;
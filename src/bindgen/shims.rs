@@ -0,0 +1,286 @@
+use quote::{ToTokens, Tokens};
+use syn;
+
+use bindgen::cfg::Cfg;
+use bindgen::const_eval::ConstantScope;
+use bindgen::documentation::Documentation;
+use bindgen::items::Type;
+use bindgen::library::ConvertResult;
+use bindgen::utilities::SynPathHelpers;
+
+// An `impl` block annotated with `cbindgen:ffi-prefix=PREFIX` gets a
+// `#[no_mangle] extern "C"` function for each of its `pub` methods. The
+// shims are written to a Rust source file for the crate to include at its
+// root, and are declared in the bindings like any other function:
+//
+//   /// cbindgen:ffi-prefix=builder
+//   impl Builder {                             extern "C" fn builder_new(w: u32) -> *mut Builder
+//       pub fn new(w: u32) -> Builder     =>   extern "C" fn builder_push(self_: *mut Builder, x: i32)
+//       pub fn push(&mut self, x: i32)         extern "C" fn builder_destroy(self_: *mut Builder)
+//   }
+
+/// A generated `extern "C"` wrapper of a method.
+#[derive(Debug, Clone)]
+pub struct Shim {
+    /// The path of the module of the `impl` block, whose items the shim
+    /// refers to
+    pub mod_path: Vec<String>,
+    /// The Rust source of the shim
+    pub source: String,
+    /// The shim parsed as an item
+    pub item: syn::Item,
+}
+
+/// Generates the shims for the `pub` methods of an `impl` block.
+pub fn generate(prefix: &str,
+                mod_path: &[String],
+                item: &syn::Item,
+                constants: &ConstantScope) -> ConvertResult<Vec<Shim>> {
+    let (generics, trait_ref, self_ty, impl_items) = match item.node {
+        syn::ItemKind::Impl(_, _, ref generics, ref trait_ref, ref self_ty, ref impl_items) => {
+            (generics, trait_ref, self_ty, impl_items)
+        }
+        _ => return Err(format!("not an impl block")),
+    };
+
+    if trait_ref.is_some() {
+        return Err(format!("shims can't be generated for trait impls"));
+    }
+    if !generics.lifetimes.is_empty() || !generics.ty_params.is_empty() {
+        return Err(format!("shims can't be generated for generic impls"));
+    }
+
+    let name = match **self_ty {
+        syn::Ty::Path(None, ref path) if path.segments.len() == 1 &&
+                                         path.segments[0].parameters.is_empty() => {
+            path.segments[0].ident.to_string()
+        }
+        _ => return Err(format!("shims can only be generated for a type named by an identifier")),
+    };
+    // The shims of a module are written outside of it
+    if constants.names_private_type(&name) {
+        return Err(format!("`{}` is not public", name));
+    }
+
+    // The shims are compiled under the same cfg as the impl
+    let cfg_attrs = match Cfg::load(&item.attrs) {
+        Some(cfg) => format!("#[cfg({})]\n", cfg),
+        None => String::new(),
+    };
+
+    let mut shims = Vec::new();
+    let mut has_constructor = false;
+
+    for impl_item in impl_items {
+        if impl_item.vis != syn::Visibility::Public {
+            continue;
+        }
+        let sig = match impl_item.node {
+            syn::ImplItemKind::Method(ref sig, _) => sig,
+            _ => continue,
+        };
+
+        let shim = match MethodShim::convert(prefix, &name, impl_item, sig, constants) {
+            Ok(shim) => shim,
+            Err(msg) => {
+                info!("skip {}::{} - ({})", name, impl_item.ident, msg);
+                continue;
+            }
+        };
+        has_constructor |= shim.constructor;
+
+        let docs = Documentation::load(&impl_item.attrs);
+        shims.push(shim.to_shim(mod_path, &cfg_attrs, &docs)?);
+    }
+
+    // Only boxes made by the constructors can be freed
+    if has_constructor {
+        if impl_items.iter().any(|x| x.ident == "destroy") {
+            warn!("not generating {}_destroy, because {} has a method named `destroy`", prefix, name);
+        } else {
+            let destructor = MethodShim {
+                name: format!("{}_destroy", prefix),
+                is_unsafe: true,
+                args: vec![(String::from("self_"), format!("*mut {}", name))],
+                ret: None,
+                body: String::from("drop(Box::from_raw(self_))"),
+                constructor: false,
+            };
+            let docs = Documentation {
+                lines: vec![format!("Frees a `{}` that was returned by `{}_*`.", name, prefix)],
+            };
+            shims.push(destructor.to_shim(mod_path, &cfg_attrs, &docs)?);
+        }
+    }
+
+    Ok(shims)
+}
+
+/// The parts of a shim for a method, before it is written out.
+struct MethodShim {
+    name: String,
+    is_unsafe: bool,
+    args: Vec<(String, String)>,
+    ret: Option<String>,
+    body: String,
+    /// Whether the method returns a new boxed instance
+    constructor: bool,
+}
+
+impl MethodShim {
+    fn convert(prefix: &str,
+               self_name: &str,
+               impl_item: &syn::ImplItem,
+               sig: &syn::MethodSig,
+               constants: &ConstantScope) -> ConvertResult<MethodShim> {
+        if !sig.generics.ty_params.is_empty() {
+            return Err(format!("method has generics"));
+        }
+        if sig.decl.variadic {
+            return Err(format!("method is variadic"));
+        }
+
+        let mut args = Vec::new();
+        let mut call_args = Vec::new();
+        // How the method is called, given its arguments
+        let mut receiver = None;
+
+        for (i, input) in sig.decl.inputs.iter().enumerate() {
+            let (arg_name, ty) = match input {
+                &syn::FnArg::SelfRef(_, mutability) => {
+                    let ptr = match mutability {
+                        syn::Mutability::Mutable => "*mut",
+                        syn::Mutability::Immutable => "*const",
+                    };
+                    args.push((String::from("self_"), format!("{} {}", ptr, self_name)));
+                    receiver = Some(String::from("(*self_)"));
+                    continue;
+                }
+                &syn::FnArg::SelfValue(_) => {
+                    args.push((String::from("self_"), format!("*mut {}", self_name)));
+                    receiver = Some(String::from("Box::from_raw(self_)"));
+                    continue;
+                }
+                &syn::FnArg::Captured(syn::Pat::Ident(_, ref ident, None), ref ty) => {
+                    if ident == "self" {
+                        return Err(format!("unsupported self type"));
+                    }
+                    (ident.to_string(), ty)
+                }
+                &syn::FnArg::Captured(_, ref ty) |
+                &syn::FnArg::Ignored(ref ty) => (format!("arg{}", i), ty),
+            };
+
+            let ty = replace_self(ty, self_name);
+            check_type(&ty, constants)?;
+            args.push((arg_name.clone(), ty));
+            call_args.push(arg_name);
+        }
+
+        // Returned references are returned as pointers, which can outlive
+        // the call
+        let ret = match sig.decl.output {
+            syn::FunctionRetTy::Default => None,
+            syn::FunctionRetTy::Ty(syn::Ty::Rptr(_, ref mut_ty)) => {
+                let ptr = match mut_ty.mutability {
+                    syn::Mutability::Mutable => "*mut",
+                    syn::Mutability::Immutable => "*const",
+                };
+                Some(format!("{} {}", ptr, replace_self(&mut_ty.ty, self_name)))
+            }
+            syn::FunctionRetTy::Ty(ref ty) => Some(replace_self(ty, self_name)),
+        };
+        if let Some(ref ret) = ret {
+            if ret != self_name {
+                check_type(ret, constants)?;
+            }
+        }
+
+        let call = match receiver {
+            Some(ref receiver) => format!("{}.{}({})", receiver, impl_item.ident, call_args.join(", ")),
+            None => format!("{}::{}({})", self_name, impl_item.ident, call_args.join(", ")),
+        };
+
+        // Associated functions returning the type are constructors, and
+        // return it boxed
+        let constructor = receiver.is_none() && ret.as_ref().map_or(false, |x| x == self_name);
+        let (ret, body) = if constructor {
+            (Some(format!("*mut {}", self_name)), format!("Box::into_raw(Box::new({}))", call))
+        } else {
+            (ret, call)
+        };
+
+        Ok(MethodShim {
+            name: format!("{}_{}", prefix, impl_item.ident),
+            is_unsafe: receiver.is_some() || sig.unsafety == syn::Unsafety::Unsafe,
+            args: args,
+            ret: ret,
+            body: body,
+            constructor: constructor,
+        })
+    }
+
+    fn to_shim(&self, mod_path: &[String], cfg_attrs: &str, docs: &Documentation) -> ConvertResult<Shim> {
+        let mut source = String::new();
+
+        for line in &docs.lines {
+            if line.is_empty() {
+                source.push_str("///\n");
+            } else {
+                source.push_str(&format!("/// {}\n", line));
+            }
+        }
+        source.push_str(cfg_attrs);
+        source.push_str("#[no_mangle]\n");
+        source.push_str(&format!("pub {}extern \"C\" fn {}({})",
+                                 if self.is_unsafe { "unsafe " } else { "" },
+                                 self.name,
+                                 self.args.iter()
+                                          .map(|x| format!("{}: {}", x.0, x.1))
+                                          .collect::<Vec<_>>()
+                                          .join(", ")));
+        if let Some(ref ret) = self.ret {
+            source.push_str(&format!(" -> {}", ret));
+        }
+        source.push_str(&format!(" {{\n    {}\n}}\n", self.body));
+
+        let item = syn::parse_item(&source).map_err(|msg| format!("generated an invalid shim: {}", msg))?;
+
+        Ok(Shim {
+            mod_path: mod_path.to_owned(),
+            source: source,
+            item: item,
+        })
+    }
+}
+
+/// Checks that a type of a shim can be declared in the bindings, which
+/// std types like `String` can't be unless they are behind a pointer.
+fn check_type(ty: &str, constants: &ConstantScope) -> ConvertResult<()> {
+    let parsed = syn::parse_type(ty).map_err(|msg| format!("invalid type `{}` - ({})", ty, msg))?;
+
+    match Type::convert(&parsed, constants)? {
+        Some(Type::Path(..)) => {
+            match parsed {
+                syn::Ty::Path(None, ref path) if constants.names_type(&path.as_path_ref()) => Ok(()),
+                _ => Err(format!("`{}` is not a type of the crate", ty)),
+            }
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Writes out a type, naming `Self` by the type of the impl.
+fn replace_self(ty: &syn::Ty, self_name: &str) -> String {
+    // Tokens are written separated by spaces
+    to_source(ty).split(' ')
+                        .map(|x| if x == "Self" { self_name } else { x })
+                        .collect::<Vec<_>>()
+                        .join(" ")
+}
+
+fn to_source<T: ToTokens>(x: &T) -> String {
+    let mut tokens = Tokens::new();
+    x.to_tokens(&mut tokens);
+    tokens.as_str().to_owned()
+}
@@ -1,5 +1,10 @@
+use quote::{ToTokens, Tokens};
 use syn::*;
+use syn::Abi;
 
+use bindgen::const_eval::ConstantScope;
+use bindgen::documentation::Documentation;
+use bindgen::items;
 use bindgen::items::*;
 use bindgen::library::*;
 
@@ -12,6 +17,40 @@ pub fn find_first_some<T>(slice: &[Option<T>]) -> Option<&T> {
     return None;
 }
 
+/// Writes out a sequence of token trees, such as the body of a macro.
+pub fn tokens_to_string(tts: &[TokenTree]) -> String {
+    let mut tokens = Tokens::new();
+    for tt in tts {
+        tt.to_tokens(&mut tokens);
+    }
+    tokens.as_str().to_owned()
+}
+
+/// Writes out a string as a C string literal.
+pub fn c_string_literal(x: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in x.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_ascii() && !c.is_control() => escaped.push(c),
+            c => {
+                // Escape everything else byte by byte, so the string
+                // stays UTF-8 encoded in the C source
+                let mut buf = [0; 4];
+                for b in c.encode_utf8(&mut buf).bytes() {
+                    escaped.push_str(&format!("\\{:03o}", b));
+                }
+            }
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
 pub trait IterHelpers : Iterator {
     fn try_skip_map<F, T, E>(&mut self, f: F) -> Result<Vec<T>, E>
         where F: FnMut(&Self::Item) -> Result<Option<T>, E>;
@@ -33,39 +72,54 @@ impl<I> IterHelpers for I where I: Iterator {
 pub trait SynItemHelpers {
     fn has_attr(&self, target: MetaItem) -> bool;
     fn get_doc_attr(&self) -> String;
+    fn get_repr_list(&self) -> Vec<String>;
+    fn get_alignment(&self) -> ConvertResult<Option<Alignment>>;
+    fn has_attr_named(&self, name: &str) -> bool;
+    fn get_deprecated(&self) -> Option<String>;
+    fn get_documentation(&self) -> Documentation;
 
     fn is_no_mangle(&self) -> bool {
         self.has_attr(MetaItem::Word(Ident::new("no_mangle")))
     }
-    fn is_repr_c(&self) -> bool {
-        let repr_args = vec![NestedMetaItem::MetaItem(MetaItem::Word(Ident::new("C")))];
-        self.has_attr(MetaItem::List(Ident::new("repr"), repr_args))
+    fn is_must_use(&self) -> bool {
+        self.has_attr_named("must_use")
+    }
+    fn is_cold(&self) -> bool {
+        self.has_attr_named("cold")
     }
-    fn is_repr_u32(&self) -> bool {
-        let repr_args = vec![NestedMetaItem::MetaItem(MetaItem::Word(Ident::new("u32")))];
-        self.has_attr(MetaItem::List(Ident::new("repr"), repr_args))
+    fn is_macro_use(&self) -> bool {
+        self.has_attr(MetaItem::Word(Ident::new("macro_use")))
     }
-    fn is_repr_u16(&self) -> bool {
-        let repr_args = vec![NestedMetaItem::MetaItem(MetaItem::Word(Ident::new("u16")))];
-        self.has_attr(MetaItem::List(Ident::new("repr"), repr_args))
+    fn has_repr(&self, repr: &str) -> bool {
+        self.get_repr_list().iter().any(|x| x == repr)
     }
-    fn is_repr_u8(&self) -> bool {
-        let repr_args = vec![NestedMetaItem::MetaItem(MetaItem::Word(Ident::new("u8")))];
-        self.has_attr(MetaItem::List(Ident::new("repr"), repr_args))
+    fn is_repr_c(&self) -> bool {
+        self.has_repr("C")
+    }
+    fn is_repr_transparent(&self) -> bool {
+        self.has_repr("transparent")
     }
     fn get_repr(&self) -> Repr {
+        // An explicit integer type takes precedence, as `repr(C, u8)`
+        // is valid for enums with fields
+        for repr in self.get_repr_list() {
+            match repr.as_ref() {
+                "u8" => return Repr::U8,
+                "u16" => return Repr::U16,
+                "u32" => return Repr::U32,
+                "u64" => return Repr::U64,
+                "usize" => return Repr::USize,
+                "i8" => return Repr::I8,
+                "i16" => return Repr::I16,
+                "i32" => return Repr::I32,
+                "i64" => return Repr::I64,
+                "isize" => return Repr::ISize,
+                _ => { }
+            }
+        }
         if self.is_repr_c() {
             return Repr::C;
         }
-        if self.is_repr_u32() {
-            return Repr::U32;
-        }
-        if self.is_repr_u16() {
-            return Repr::U16;
-        }
-        if self.is_repr_u8() {
-            return Repr::U8;
-        }
         Repr::None
     }
 }
@@ -88,6 +142,23 @@ impl SynItemHelpers for Item {
         }
         doc
     }
+    fn get_repr_list(&self) -> Vec<String> {
+        get_repr_list(&self.attrs)
+    }
+    fn get_alignment(&self) -> ConvertResult<Option<Alignment>> {
+        get_alignment(&self.attrs)
+    }
+    fn has_attr_named(&self, name: &str) -> bool {
+        self.attrs
+            .iter()
+            .any(|ref attr| attr.style == AttrStyle::Outer && attr.name() == name)
+    }
+    fn get_deprecated(&self) -> Option<String> {
+        get_deprecated(&self.attrs)
+    }
+    fn get_documentation(&self) -> Documentation {
+        Documentation::load(&self.attrs)
+    }
 }
 impl SynItemHelpers for ForeignItem {
     fn has_attr(&self, target: MetaItem) -> bool {
@@ -108,31 +179,151 @@ impl SynItemHelpers for ForeignItem {
         }
         doc
     }
+    fn get_repr_list(&self) -> Vec<String> {
+        get_repr_list(&self.attrs)
+    }
+    fn get_alignment(&self) -> ConvertResult<Option<Alignment>> {
+        get_alignment(&self.attrs)
+    }
+    fn has_attr_named(&self, name: &str) -> bool {
+        self.attrs
+            .iter()
+            .any(|ref attr| attr.style == AttrStyle::Outer && attr.name() == name)
+    }
+    fn get_deprecated(&self) -> Option<String> {
+        get_deprecated(&self.attrs)
+    }
+    fn get_documentation(&self) -> Documentation {
+        Documentation::load(&self.attrs)
+    }
+}
+
+/// Collects the arguments of every `#[repr(..)]` attribute, so that
+/// `#[repr(C, u8)]` and `#[repr(C)] #[repr(u8)]` are treated the same.
+fn get_repr_list(attrs: &[Attribute]) -> Vec<String> {
+    let mut result = Vec::new();
+    for attr in attrs {
+        if attr.style != AttrStyle::Outer {
+            continue;
+        }
+        if let MetaItem::List(ref name, ref args) = attr.value {
+            if name != "repr" {
+                continue;
+            }
+            for arg in args {
+                if let &NestedMetaItem::MetaItem(MetaItem::Word(ref word)) = arg {
+                    result.push(word.to_string());
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Finds the `packed`, `packed(N)` or `align(N)` argument of the
+/// `#[repr(..)]` attributes.
+fn get_alignment(attrs: &[Attribute]) -> ConvertResult<Option<Alignment>> {
+    let mut result = None;
+    for attr in attrs {
+        if attr.style != AttrStyle::Outer {
+            continue;
+        }
+        if let MetaItem::List(ref name, ref args) = attr.value {
+            if name != "repr" {
+                continue;
+            }
+            for arg in args {
+                let alignment = match arg {
+                    &NestedMetaItem::MetaItem(MetaItem::Word(ref word)) if word == "packed" => {
+                        Alignment::Packed
+                    }
+                    &NestedMetaItem::MetaItem(MetaItem::List(ref word, ref value)) if word == "packed" => {
+                        match value.as_slice() {
+                            &[NestedMetaItem::Literal(Lit::Int(1, _))] => Alignment::Packed,
+                            _ => return Err(format!("only packed(1) is supported")),
+                        }
+                    }
+                    &NestedMetaItem::MetaItem(MetaItem::List(ref word, ref value)) if word == "align" => {
+                        match value.as_slice() {
+                            &[NestedMetaItem::Literal(Lit::Int(n, _))] if n.is_power_of_two() => {
+                                Alignment::Align(n)
+                            }
+                            _ => return Err(format!("invalid align repr")),
+                        }
+                    }
+                    _ => continue,
+                };
+
+                if result.is_some() {
+                    return Err(format!("conflicting packed and align reprs"));
+                }
+                result = Some(alignment);
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Finds the note of a `#[deprecated]` attribute, which is empty if it
+/// doesn't have one.
+fn get_deprecated(attrs: &[Attribute]) -> Option<String> {
+    for attr in attrs {
+        if attr.style != AttrStyle::Outer || attr.name() != "deprecated" {
+            continue;
+        }
+        let note = match attr.value {
+            MetaItem::NameValue(_, Lit::Str(ref note, _)) => note.clone(),
+            MetaItem::List(_, ref args) => {
+                args.iter()
+                    .filter_map(|arg| match arg {
+                        &NestedMetaItem::MetaItem(MetaItem::NameValue(ref key, Lit::Str(ref value, _)))
+                            if key == "note" => Some(value.clone()),
+                        _ => None,
+                    })
+                    .next()
+                    .unwrap_or_default()
+            }
+            _ => String::new(),
+        };
+        return Some(note);
+    }
+    None
 }
 
 pub trait SynAbiHelpers {
-    fn is_c(&self) -> bool;
+    /// The calling convention of the ABI, if it can be called from C.
+    fn c_abi(&self) -> Option<items::Abi>;
+
+    fn is_c(&self) -> bool {
+        self.c_abi().is_some()
+    }
 }
 impl SynAbiHelpers for Option<Abi> {
-    fn is_c(&self) -> bool {
-        self == &Some(Abi::Named(String::from("C")))
+    fn c_abi(&self) -> Option<items::Abi> {
+        self.as_ref().and_then(|x| x.c_abi())
     }
 }
 impl SynAbiHelpers for Abi {
-    fn is_c(&self) -> bool {
-        self == &Abi::Named(String::from("C"))
+    fn c_abi(&self) -> Option<items::Abi> {
+        match self {
+            &Abi::Named(ref name) => items::Abi::maybe(name),
+            // A bare `extern` is `extern "C"`
+            &Abi::Rust => Some(items::Abi::C),
+        }
     }
 }
 
 pub trait SynFnRetTyHelpers {
-    fn as_type(&self) -> ConvertResult<Type>;
+    fn as_type(&self, constants: &ConstantScope) -> ConvertResult<Type>;
 }
 impl SynFnRetTyHelpers for FunctionRetTy {
-    fn as_type(&self) -> ConvertResult<Type> {
+    fn as_type(&self, constants: &ConstantScope) -> ConvertResult<Type> {
         match self {
             &FunctionRetTy::Default => Ok(Type::Primitive(PrimitiveType::Void)),
+            // Functions that never return are declared as returning `void`
+            &FunctionRetTy::Ty(Ty::Never) => Ok(Type::Primitive(PrimitiveType::Void)),
             &FunctionRetTy::Ty(ref t) => {
-                if let Some(x) = try!(Type::convert(t)) {
+                if let Some(x) = try!(Type::convert(t, constants)) {
                     Ok(x)
                 } else {
                     Ok(Type::Primitive(PrimitiveType::Void))
@@ -143,13 +334,13 @@ impl SynFnRetTyHelpers for FunctionRetTy {
 }
 
 pub trait SynFnArgHelpers {
-    fn as_ident_and_type(&self) -> ConvertResult<Option<(String, Type)>>;
+    fn as_ident_and_type(&self, constants: &ConstantScope) -> ConvertResult<Option<(String, Type)>>;
 }
 impl SynFnArgHelpers for FnArg {
-    fn as_ident_and_type(&self) -> ConvertResult<Option<(String, Type)>> {
+    fn as_ident_and_type(&self, constants: &ConstantScope) -> ConvertResult<Option<(String, Type)>> {
         match self {
             &FnArg::Captured(Pat::Ident(_, ref ident, _), ref ty) => {
-                if let Some(x) = try!(Type::convert(ty)) {
+                if let Some(x) = try!(Type::convert(ty, constants)) {
                     Ok(Some((ident.to_string(), x)))
                 } else {
                     Ok(None)
@@ -161,12 +352,12 @@ impl SynFnArgHelpers for FnArg {
 }
 
 pub trait SynFieldHelpers {
-    fn as_ident_and_type(&self) -> ConvertResult<Option<(String, Type)>>;
+    fn as_ident_and_type(&self, constants: &ConstantScope) -> ConvertResult<Option<(String, Type)>>;
 }
 impl SynFieldHelpers for Field {
-    fn as_ident_and_type(&self) -> ConvertResult<Option<(String, Type)>> {
+    fn as_ident_and_type(&self, constants: &ConstantScope) -> ConvertResult<Option<(String, Type)>> {
         let ident = try!(self.ident.as_ref().ok_or(format!("missing ident"))).clone();
-        let converted_ty = try!(Type::convert(&self.ty));
+        let converted_ty = try!(Type::convert(&self.ty, constants));
 
         if let Some(x) = converted_ty {
             Ok(Some((ident.to_string(), x)))
@@ -176,36 +367,89 @@ impl SynFieldHelpers for Field {
     }
 }
 
+/// Modules of the standard library and libc that define C types
+const C_TYPE_MODULES: &'static [&'static [&'static str]] = &[
+    &["std", "os", "raw"],
+    &["core", "os", "raw"],
+    &["std", "ffi"],
+    &["core", "ffi"],
+    &["std", "primitive"],
+    &["core", "primitive"],
+    &["std", "num"],
+    &["core", "num"],
+    &["libc"],
+];
+
 pub trait SynPathHelpers {
-    fn convert_to_generic_single_segment(&self) -> ConvertResult<(String, Vec<Type>)>;
+    fn as_path_ref(&self) -> PathRef;
+    fn is_c_type_path(&self) -> bool;
+    fn convert_to_generic_path(&self, constants: &ConstantScope) -> ConvertResult<(String, Vec<Type>)>;
 }
 impl SynPathHelpers for Path {
-    fn convert_to_generic_single_segment(&self) -> ConvertResult<(String, Vec<Type>)> {
-        if self.segments.len() != 1 {
-            return Err(format!("Path is not a single segment"));
+    /// The path as it is written in rust.
+    fn as_path_ref(&self) -> PathRef {
+        let path = self.segments.iter()
+                                .map(|x| x.ident.to_string())
+                                .collect::<Vec<_>>()
+                                .join("::");
+
+        if self.global {
+            format!("::{}", path)
+        } else {
+            path
+        }
+    }
+
+    /// Whether this path may name a primitive type, either directly or
+    /// through one of the well known modules that define C types.
+    fn is_c_type_path(&self) -> bool {
+        if self.segments.len() == 1 {
+            return !self.global;
+        }
+
+        let module = &self.segments[..self.segments.len() - 1];
+
+        C_TYPE_MODULES.iter().any(|known| {
+            known.len() == module.len() &&
+            known.iter().zip(module.iter()).all(|(a, b)| b.ident == *a)
+        })
+    }
+
+    /// Converts a path to the path of the item it refers to, as written,
+    /// and its generic parameters.
+    fn convert_to_generic_path(&self, constants: &ConstantScope) -> ConvertResult<(String, Vec<Type>)> {
+        let last = match self.segments.last() {
+            Some(last) => last,
+            None => return Err(format!("Path is empty")),
+        };
+
+        for segment in &self.segments[..self.segments.len() - 1] {
+            if !segment.parameters.is_empty() {
+                return Err(format!("Path contains generic parameters before the last segment"));
+            }
         }
 
-        let name = self.segments[0].ident.to_string();
+        let path = self.as_path_ref();
 
-        if name == "PhantomData" {
-            return Ok((name, Vec::new()));
+        if last.ident == "PhantomData" {
+            return Ok((path, Vec::new()));
         }
 
-        let generics = match &self.segments[0].parameters {
+        // Lifetimes don't affect the layout of a type, so they are ignored
+        let generics = match &last.parameters {
             &PathParameters::AngleBracketed(ref d) => {
-                if !d.lifetimes.is_empty() ||
-                   !d.bindings.is_empty() {
-                    return Err(format!("Generic parameter contains bindings, or lifetimes"));
+                if !d.bindings.is_empty() {
+                    return Err(format!("Generic parameter contains bindings"));
                 }
 
-                try!(d.types.iter()
-                            .try_skip_map(|x| Type::convert(x)))
+                d.types.iter()
+                       .try_skip_map(|x| Type::convert(x, constants))?
             }
             &PathParameters::Parenthesized(_) => {
                 return Err(format!("Path contains parentheses"));
             }
         };
 
-        Ok((name, generics))
+        Ok((path, generics))
     }
 }
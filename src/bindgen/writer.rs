@@ -6,7 +6,6 @@ use bindgen::config::{Config, Braces};
 
 pub enum ListType<'a> {
     Join(&'a str),
-    Cap(&'a str),
 }
 
 pub struct NullFile;
@@ -155,33 +154,6 @@ impl<'a, F: Write> SourceWriter<'a, F> {
                         self.write(&text);
                     }
                 }
-                ListType::Cap(text) => {
-                    self.write(&text);
-                }
-            }
-
-            if i != items.len() - 1 {
-                self.new_line();
-            }
-        }
-        self.pop_tab();
-    }
-
-    pub fn write_vertical_source_list<'b, S: Source>(&mut self, items: &Vec<S>, list_type: ListType<'b>) {
-        let align_length = self.line_length_for_align();
-        self.push_set_spaces(align_length);
-        for (i, ref item) in items.iter().enumerate() {
-            item.write(self.config, self);
-
-            match list_type {
-                ListType::Join(text) => {
-                    if i != items.len() - 1 {
-                        self.write(&text);
-                    }
-                }
-                ListType::Cap(text) => {
-                    self.write(&text);
-                }
             }
 
             if i != items.len() - 1 {
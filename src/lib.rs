@@ -1,5 +1,6 @@
 #[macro_use]
 extern crate log;
+extern crate quote;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
@@ -4,6 +4,7 @@ use std::path::Path;
 extern crate clap;
 #[macro_use]
 extern crate log;
+extern crate quote;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
@@ -50,6 +51,11 @@ fn main() {
                          .value_name("OUTPUT")
                          .help("the path to output the bindings to")
                          .required(false))
+                    .arg(Arg::with_name("shims")
+                         .long("shims")
+                         .value_name("SHIMS")
+                         .help("the path to output the Rust source of the shims for `ffi-prefix` impls to")
+                         .required(false))
                     .get_matches();
 
     match matches.occurrences_of("v") {
@@ -61,7 +67,13 @@ fn main() {
     let input = matches.value_of("INPUT").unwrap();
 
     let mut config = match matches.value_of("config") {
-        Some(c) => Config::from_file(c).unwrap(),
+        Some(c) => match Config::from_file(c) {
+            Ok(config) => config,
+            Err(msg) => {
+                error!("{}", msg);
+                return;
+            }
+        },
         None => Config::from_root_or_default(&input),
     };
 
@@ -118,6 +130,16 @@ fn main() {
         },
     };
 
+    match matches.value_of("shims") {
+        Some(file) => {
+            built.write_shims_to_file(file);
+        }
+        None if built.has_shims() => {
+            warn!("the bindings declare shims for `ffi-prefix` impls, which aren't written without --shims");
+        }
+        None => {}
+    }
+
     match matches.value_of("out") {
         Some(file) => {
             built.write_to_file(file);